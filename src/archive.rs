@@ -0,0 +1,41 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A complete experiment including all of its runs, in a form that can be serialized (e.g. to JSON) and imported into
+/// another database. Created through `Experiment::to_archive` and imported through `Experiment::import_archive`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExperimentArchive {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub researcher: String,
+    pub variables: Vec<ArchivedVariable>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    pub runs: Vec<ArchivedRun>,
+}
+
+/// A variable of an `ExperimentArchive`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedVariable {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub unit: String,
+}
+
+/// A run of an `ExperimentArchive`. Measurements are stored by variable name, optional variables without a value are
+/// stored as `None`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedRun {
+    pub id: String,
+    pub run_number: usize,
+    #[serde(default)]
+    pub label: Option<String>,
+    pub timestamp: SystemTime,
+    pub measurements: BTreeMap<String, Option<String>>,
+}
@@ -1,49 +1,354 @@
-use std::{io::Write, path::PathBuf, time::SystemTime};
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
 
 use anyhow::{anyhow, bail, Context, Result};
-use chrono::{Local, NaiveDateTime};
-use clap::{Parser, Subcommand};
-use experiment_archiver::Experiment;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use clap::{CommandFactory, Parser, Subcommand};
+use experiment_archiver::{
+    missing_schema_columns, ping, replay_fallback_file, ConnectionError, ExarError, Experiment,
+    ExperimentArchive, GlobalStats, MeasurementPredicate, RawRun, Run,
+};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use tabled::{
     builder::Builder,
     settings::{object::Rows, Modify, Style, Width},
 };
 
+/// Exit code if the command succeeded
+const EXIT_SUCCESS: i32 = 0;
+/// Exit code for all errors that don't have a more specific exit code
+const EXIT_FAILURE: i32 = 1;
+/// Exit code if the requested experiment or run does not exist
+const EXIT_NOT_FOUND: i32 = 2;
+/// Exit code if no connection to the database could be established
+const EXIT_DATABASE_CONNECTION: i32 = 3;
+/// Exit code for invalid command line arguments, or if a command would have to prompt for input but stdin
+/// is not interactive
+const EXIT_USAGE: i32 = 4;
+
+/// Error for requested data (experiments, runs) that does not exist in the database
+#[derive(Debug)]
+struct NotFoundError(String);
+
+impl std::fmt::Display for NotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NotFoundError {}
+
+/// Error for invalid usage of the CLI that clap can't detect, e.g. malformed argument values
+#[derive(Debug)]
+struct UsageError(String);
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Maps an error to the exit code of the CLI, based on the errors in its chain of causes
+fn exit_code_for_error(error: &anyhow::Error) -> i32 {
+    if error.chain().any(|cause| cause.is::<ConnectionError>()) {
+        EXIT_DATABASE_CONNECTION
+    } else if error.chain().any(|cause| cause.is::<NotFoundError>())
+        || matches!(
+            ExarError::find_in(error),
+            Some(ExarError::ExperimentNotFound(_))
+        )
+    {
+        EXIT_NOT_FOUND
+    } else if error.chain().any(|cause| cause.is::<UsageError>()) {
+        EXIT_USAGE
+    } else {
+        EXIT_FAILURE
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "Experiment Archive CLI")]
 #[command(author = "Pascal Bormann <pascal@pascalbormann.de>")]
 #[command(version)]
 #[command(about = "CLI for the experiment-archive crate", long_about = None)]
+#[command(
+    after_help = "Exit codes: 0 on success, 1 for general errors, 2 if the requested data was not found, 3 if no connection to the database could be established, 4 for invalid usage"
+)]
 struct Args {
     #[command(subcommand)]
     command: Commands,
+    #[arg(long, global = true, help = "Host of the database")]
+    host: Option<String>,
+    #[arg(long, global = true, help = "Port of the database")]
+    port: Option<String>,
+    #[arg(long, global = true, help = "User for the database connection")]
+    user: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        help = "Password for the database connection. Note that command line arguments are visible to other users of the system"
+    )]
+    password: Option<String>,
+    #[arg(long, global = true, help = "Name of the database")]
+    dbname: Option<String>,
+}
+
+impl Args {
+    /// Returns the connection configuration given through the command line flags, if all of them are set
+    fn connection_configuration(&self) -> Option<Configuration> {
+        Some(Configuration {
+            user: self.user.clone()?,
+            password: self.password.clone()?,
+            host: self.host.clone()?,
+            port: self.port.clone()?,
+            database_name: self.dbname.clone()?,
+        })
+    }
+
+    /// Overrides the connection parameters that are given through command line flags
+    fn apply_connection_flags(&self) {
+        let flags = [
+            ("PSQL_USER", &self.user),
+            ("PSQL_PWD", &self.password),
+            ("PSQL_HOST", &self.host),
+            ("PSQL_PORT", &self.port),
+            ("PSQL_DBNAME", &self.dbname),
+        ];
+        for (var, value) in flags {
+            if let Some(value) = value {
+                std::env::set_var(var, value);
+            }
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     Configure {},
+    #[command(
+        about = "Check the database connection and schema, and print which connection settings are set"
+    )]
+    Doctor {},
+    #[command(
+        about = "Print a shell completion script, e.g. `phd-ex-cli completions bash > /etc/bash_completion.d/phd-ex-cli`"
+    )]
+    Completions {
+        shell: clap_complete::Shell,
+    },
     ListExperiments {
         #[arg(short, long, default_value_t = false)]
         as_csv: bool,
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with = "as_csv",
+            help = "Print one JSON object per row (JSON Lines)"
+        )]
+        ndjson: bool,
+        #[command(flatten)]
+        wrapping: WrappingArgs,
+        #[arg(long, help = "Only list experiments that have this tag")]
+        tag: Option<String>,
     },
     ListRuns {
         experiment_name: String,
         #[arg(short, long, default_value_t = false)]
         as_csv: bool,
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with = "as_csv",
+            help = "Print one JSON object per row (JSON Lines)"
+        )]
+        ndjson: bool,
+        #[command(flatten)]
+        wrapping: WrappingArgs,
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with_all = ["as_csv", "ndjson"],
+            help = "Print each run as a single line of the form 'id date var1=val1 var2=val2 ...'"
+        )]
+        oneline: bool,
+        #[arg(long, help = "Print at most this many runs")]
+        limit: Option<usize>,
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "Skip this many runs (ordered by run number) before printing"
+        )]
+        offset: usize,
+        #[arg(
+            long = "where",
+            value_name = "PREDICATE",
+            help = "Only list runs whose measurements satisfy this predicate, e.g. 'Runtime>500' or 'Dataset=ds1'. Supported operators are =, !=, <, <=, > and >=. Can be given multiple times, in which case runs must satisfy all predicates"
+        )]
+        predicates: Vec<MeasurementPredicate>,
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with_all = ["limit", "offset", "predicates"],
+            help = "Only print the most recent run, with all of its values"
+        )]
+        latest: bool,
     },
     PrintRun {
-        run_id: String,
+        #[arg(help = "The ID of the run, or its label if --experiment is given")]
+        run: String,
+        #[arg(
+            short,
+            long,
+            help = "Name of the experiment that the run belongs to. If given, the run is looked up by its label"
+        )]
+        experiment: Option<String>,
         #[arg(short, long, default_value_t = false)]
         as_csv: bool,
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with = "as_csv",
+            help = "Print one JSON object per row (JSON Lines)"
+        )]
+        ndjson: bool,
+        #[command(flatten)]
+        wrapping: WrappingArgs,
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with_all = ["as_csv", "ndjson"],
+            help = "Print the run as a single line of the form 'id date var1=val1 var2=val2 ...'"
+        )]
+        oneline: bool,
     },
     PrintAllRuns {
         experiment_name: String,
         #[arg(short, long, default_value_t = false)]
         as_csv: bool,
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with = "as_csv",
+            help = "Print one JSON object per row (JSON Lines)"
+        )]
+        ndjson: bool,
+        #[command(flatten)]
+        wrapping: WrappingArgs,
+    },
+    #[cfg(feature = "xlsx")]
+    #[command(
+        about = "Export all runs of an experiment and statistics of their numeric values to an Excel workbook"
+    )]
+    Export {
+        experiment_name: String,
+        #[arg(short, long, help = "Path of the .xlsx file to write")]
+        output: PathBuf,
+    },
+    #[cfg(feature = "parquet")]
+    #[command(
+        about = "Export all runs of an experiment to a Parquet file with one row per run and one column per variable"
+    )]
+    ExportParquet {
+        experiment_name: String,
+        #[arg(short, long, help = "Path of the .parquet file to write")]
+        output: PathBuf,
+    },
+    #[cfg(feature = "metrics")]
+    #[command(
+        about = "Serve the values of the latest run of an experiment as Prometheus metrics at /metrics. The database is polled periodically and never written to"
+    )]
+    ServeMetrics {
+        experiment_name: String,
+        #[arg(
+            long,
+            default_value_t = 9185,
+            help = "Port of the HTTP server that serves the metrics"
+        )]
+        metrics_port: u16,
+        #[arg(
+            long,
+            default_value = "127.0.0.1",
+            help = "Address of the HTTP server that serves the metrics. Use 0.0.0.0 to serve the metrics to other hosts"
+        )]
+        bind_address: std::net::IpAddr,
+        #[arg(
+            short,
+            long,
+            default_value_t = 15,
+            help = "Seconds between fetching the latest run from the database"
+        )]
+        interval: u64,
+    },
+    #[command(
+        about = "Print the total number of experiments, variables, runs and measurements in the database"
+    )]
+    Stats {
+        #[arg(short, long, default_value_t = false)]
+        as_csv: bool,
+        #[arg(
+            long,
+            default_value_t = false,
+            conflicts_with = "as_csv",
+            help = "Print one JSON object per row (JSON Lines)"
+        )]
+        ndjson: bool,
+        #[command(flatten)]
+        wrapping: WrappingArgs,
     },
     DeleteExperiment {
         experiment_name: String,
+        #[arg(
+            short,
+            long,
+            default_value_t = false,
+            help = "Skip the confirmation prompt"
+        )]
+        yes: bool,
+    },
+    #[command(
+        about = "Rename an experiment. Fails if an experiment with the new name already exists"
+    )]
+    Rename {
+        old_name: String,
+        new_name: String,
+    },
+    #[command(
+        about = "Insert all runs from a fallback file into the database, e.g. after the database was unreachable. The file is truncated afterwards"
+    )]
+    Replay {
+        fallback_file: PathBuf,
+    },
+    #[command(
+        about = "Write an experiment with all of its runs as JSON, e.g. to move it to another database with 'import'"
+    )]
+    Archive {
+        experiment_name: String,
+        #[arg(
+            short,
+            long,
+            help = "Path of the JSON file to write. Prints to stdout if omitted"
+        )]
+        output: Option<PathBuf>,
+    },
+    #[command(
+        about = "Print a JSON Schema that describes the values of a run of an experiment, with one property per variable"
+    )]
+    Schema {
+        experiment_name: String,
+    },
+    #[command(about = "Insert an experiment from a JSON file that was written by 'archive'")]
+    Import {
+        file: PathBuf,
     },
     DeleteRuns {
         experiment_name: String,
@@ -51,10 +356,100 @@ enum Commands {
             help = "The ID(s) of all runs that should be deleted. Can be a single number (e.g. \"4\") to delete one run, a comma-separated list (e.g. \"1,2,4\") to delete multiple runs, or an inclusive range (e.g. \"1-6\") to delete a range of consecutive runs"
         )]
         run_numbers: String,
+        #[arg(
+            short,
+            long,
+            default_value_t = false,
+            help = "Skip the confirmation prompt"
+        )]
+        yes: bool,
+    },
+    #[command(about = "Delete all runs of an experiment that are older than a given age")]
+    Prune {
+        experiment_name: String,
+        #[arg(
+            long,
+            value_parser = parse_age,
+            help = "Delete all runs that are older than this, as a number followed by s, m, h, d or w (e.g. \"30d\")"
+        )]
+        older_than: std::time::Duration,
+        #[arg(
+            short,
+            long,
+            default_value_t = false,
+            help = "Skip the confirmation prompt"
+        )]
+        yes: bool,
     },
 }
 
-#[derive(Serialize, Deserialize)]
+/// Environment variable with a passphrase for encrypting the database password in the configuration file. If it is not
+/// set, the password is stored in plaintext
+const CONFIG_PASSPHRASE_VAR: &str = "PHD_EX_CLI_CONFIG_PASSPHRASE";
+/// Prefix of encrypted passwords in the configuration file. It is followed by the base64-encoded salt, nonce and
+/// ciphertext
+const ENCRYPTED_PASSWORD_PREFIX: &str = "enc:v1:";
+const PASSPHRASE_SALT_LENGTH: usize = 16;
+const PASSWORD_NONCE_LENGTH: usize = 12;
+/// Number of PBKDF2 iterations for deriving the encryption key from the passphrase
+const PASSPHRASE_KDF_ROUNDS: u32 = 100_000;
+
+fn derive_password_key(passphrase: &str, salt: &[u8]) -> Key {
+    let mut key = Key::default();
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(
+        passphrase.as_bytes(),
+        salt,
+        PASSPHRASE_KDF_ROUNDS,
+        &mut key,
+    );
+    key
+}
+
+/// Encrypts `password` with a key derived from `passphrase` (PBKDF2-SHA256 with a random salt, then
+/// ChaCha20-Poly1305 with a random nonce). The result can be stored in the configuration file in place of the password
+fn encrypt_password(password: &str, passphrase: &str) -> Result<String> {
+    let mut salt_and_nonce = [0u8; PASSPHRASE_SALT_LENGTH + PASSWORD_NONCE_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt_and_nonce);
+    let (salt, nonce) = salt_and_nonce.split_at(PASSPHRASE_SALT_LENGTH);
+
+    let cipher = ChaCha20Poly1305::new(&derive_password_key(passphrase, salt));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(nonce), password.as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt password"))?;
+
+    let mut encoded = salt_and_nonce.to_vec();
+    encoded.extend(ciphertext);
+    Ok(format!(
+        "{ENCRYPTED_PASSWORD_PREFIX}{}",
+        BASE64.encode(encoded)
+    ))
+}
+
+/// Decrypts a password that was encrypted with `encrypt_password`. Fails if the passphrase is wrong or the encrypted
+/// password was modified
+fn decrypt_password(encrypted_password: &str, passphrase: &str) -> Result<String> {
+    let encoded = encrypted_password
+        .strip_prefix(ENCRYPTED_PASSWORD_PREFIX)
+        .ok_or_else(|| anyhow!("Password is not encrypted"))?;
+    let decoded = BASE64
+        .decode(encoded)
+        .context("Encrypted password is not valid base64")?;
+    if decoded.len() < PASSPHRASE_SALT_LENGTH + PASSWORD_NONCE_LENGTH {
+        bail!("Encrypted password is too short");
+    }
+    let (salt, rest) = decoded.split_at(PASSPHRASE_SALT_LENGTH);
+    let (nonce, ciphertext) = rest.split_at(PASSWORD_NONCE_LENGTH);
+
+    let cipher = ChaCha20Poly1305::new(&derive_password_key(passphrase, salt));
+    let password = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            anyhow!("Failed to decrypt password. Either {CONFIG_PASSPHRASE_VAR} is wrong or the configuration file is corrupted")
+        })?;
+    String::from_utf8(password).context("Decrypted password is not valid UTF-8")
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct Configuration {
     user: String,
     password: String,
@@ -73,14 +468,33 @@ impl Configuration {
         std::env::set_var("PSQL_DBNAME", &self.database_name);
     }
 
-    /// Store this configuration to disk
+    /// Store this configuration to disk. If the `PHD_EX_CLI_CONFIG_PASSPHRASE` environment variable is set, the
+    /// password is encrypted with this passphrase
     pub fn store(&self) -> Result<()> {
         let path = Self::default_path()?;
-        let config_json = serde_json::to_string_pretty(self)
+        let mut stored_config = self.clone();
+        if let Ok(passphrase) = std::env::var(CONFIG_PASSPHRASE_VAR) {
+            stored_config.password = encrypt_password(&self.password, &passphrase)?;
+        }
+        let config_json = serde_json::to_string_pretty(&stored_config)
             .context("Failed to convert configuration to JSON format")?;
         std::fs::write(path, config_json).context("Failed to write configuration file")
     }
 
+    /// Decrypts the password of a configuration that was loaded from disk, if it is encrypted. Returns `true` if the
+    /// password is stored in plaintext although a `passphrase` is given, in which case the configuration should be
+    /// stored again to encrypt the password
+    fn decrypt_stored_password(&mut self, passphrase: Option<&str>) -> Result<bool> {
+        if !self.password.starts_with(ENCRYPTED_PASSWORD_PREFIX) {
+            return Ok(passphrase.is_some());
+        }
+        let passphrase = passphrase.ok_or_else(|| {
+            UsageError(format!("The password in the configuration file is encrypted. Set {CONFIG_PASSPHRASE_VAR} to the passphrase that was used to encrypt it"))
+        })?;
+        self.password = decrypt_password(&self.password, passphrase)?;
+        Ok(false)
+    }
+
     /// Returns the default file path of the config file
     pub fn default_path() -> Result<PathBuf> {
         let user_config_dir = dirs::config_dir()
@@ -99,7 +513,7 @@ fn load_config() -> Result<bool> {
     let config_file_parent_dir = config_file_path
         .parent()
         .expect("Could not get parent directory of default config path");
-    std::fs::create_dir_all(&config_file_parent_dir)
+    std::fs::create_dir_all(config_file_parent_dir)
         .context("Could not create configuration directory")?;
 
     if !config_file_path.exists() {
@@ -108,7 +522,17 @@ fn load_config() -> Result<bool> {
         match serde_json::from_str::<Configuration>(
             &std::fs::read_to_string(&config_file_path).context("Could not load config file")?,
         ) {
-            Ok(parsed_config) => {
+            Ok(mut parsed_config) => {
+                let passphrase = std::env::var(CONFIG_PASSPHRASE_VAR).ok();
+                let is_plaintext = parsed_config
+                    .decrypt_stored_password(passphrase.as_deref())
+                    .context("Failed to decrypt password in configuration file")?;
+                if is_plaintext {
+                    parsed_config
+                        .store()
+                        .context("Failed to encrypt password in configuration file")?;
+                    eprintln!("Encrypted the password in the configuration file with the passphrase from {CONFIG_PASSPHRASE_VAR}");
+                }
                 parsed_config.apply();
                 Ok(true)
             }
@@ -120,55 +544,156 @@ fn load_config() -> Result<bool> {
     }
 }
 
+/// Returns `true` if stdin is connected to an interactive terminal. If it is not (e.g. when stdin is piped or
+/// when running in CI), the CLI must not prompt for input since the prompt would either block or read garbage
+fn stdin_is_interactive() -> bool {
+    termion::is_tty(&std::io::stdin())
+}
+
+/// Returns `true` if all environment variables required for connecting to the database are set
+fn connection_env_vars_are_set() -> bool {
+    [
+        "PSQL_USER",
+        "PSQL_PWD",
+        "PSQL_HOST",
+        "PSQL_PORT",
+        "PSQL_DBNAME",
+    ]
+    .iter()
+    .all(|var| std::env::var(var).is_ok())
+}
+
+/// Prints `prompt` and reads a single line from `reader`, returning the trimmed line. Returns an error if `reader`
+/// has no more input instead of silently returning an empty string
+fn read_string_with_prompt<R: BufRead>(prompt: &str, mut reader: R) -> Result<String> {
+    print!("{prompt}");
+    std::io::stdout().flush()?;
+    let mut line = String::default();
+    let bytes_read = reader.read_line(&mut line).context("Failed to read line")?;
+    if bytes_read == 0 {
+        bail!("Reached end of input while waiting for a response to prompt \"{prompt}\"");
+    }
+    Ok(line.trim().to_owned())
+}
+
+/// Prints `prompt` as a yes/no question and reads the answer from `reader`. Only an explicit "y" counts as yes
+fn read_bool_with_prompt<R: BufRead>(prompt: &str, reader: R) -> Result<bool> {
+    let answer = read_string_with_prompt(&format!("{prompt} (y/n) "), reader)?;
+    Ok(answer == "y")
+}
+
+/// Asks the user to confirm a destructive operation. If `skip_confirmation` is set, no prompt is shown. If stdin is
+/// not interactive, an error is returned instead of prompting
+fn confirm(prompt: &str, skip_confirmation: bool) -> Result<bool> {
+    if skip_confirmation {
+        return Ok(true);
+    }
+    if !stdin_is_interactive() {
+        return Err(UsageError("Confirmation required, but stdin is not an interactive terminal. Re-run with --yes to skip the confirmation prompt".into()).into());
+    }
+    read_bool_with_prompt(prompt, std::io::stdin().lock())
+}
+
 /// Run tool configuration
 fn configure() -> Result<()> {
-    let mut host = String::default();
-    print!("Enter hostname of postgres database: ");
-    std::io::stdout().flush()?;
-    std::io::stdin()
-        .read_line(&mut host)
-        .context("Failed to read line")?;
+    if !stdin_is_interactive() {
+        return Err(UsageError("Configuration requires an interactive terminal. Either run the `configure` command from a terminal, or provide the database connection through the PSQL_USER, PSQL_PWD, PSQL_HOST, PSQL_PORT and PSQL_DBNAME environment variables".into()).into());
+    }
 
-    let mut port = String::default();
-    print!("Enter port of postgres database: ");
-    std::io::stdout().flush()?;
-    std::io::stdin()
-        .read_line(&mut port)
-        .context("Failed to read line")?;
+    let mut stdin = std::io::stdin().lock();
+    loop {
+        let host = read_string_with_prompt("Enter hostname of postgres database: ", &mut stdin)?;
+        let port = read_string_with_prompt("Enter port of postgres database: ", &mut stdin)?;
+        let user = read_string_with_prompt("Enter username of postgres database: ", &mut stdin)?;
+        let password = read_string_with_prompt("Enter password: ", &mut stdin)?;
+        let database_name = read_string_with_prompt("Enter name of database: ", &mut stdin)?;
+
+        let config = Configuration {
+            database_name,
+            user,
+            password,
+            host,
+            port,
+        };
+        // The configuration is stored even if the connection test fails, since the database might just be
+        // temporarily unavailable
+        config
+            .store()
+            .context("Failed to store new configuration to disk")?;
+        config.apply();
+
+        match ping() {
+            Ok(()) => {
+                println!("Successfully connected to the database");
+                return Ok(());
+            }
+            Err(why) => {
+                eprintln!(
+                    "Warning: Could not connect to the database with this configuration ({why:#})"
+                );
+                if !read_bool_with_prompt("Do you want to re-enter the configuration?", &mut stdin)?
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
 
-    let mut user = String::default();
-    print!("Enter username of postgres database: ");
-    std::io::stdout().flush()?;
-    std::io::stdin()
-        .read_line(&mut user)
-        .context("Failed to read line")?;
+/// The format in which `GenericTable`s are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// A table whose cells are wrapped at `max_column_width` characters, or never wrapped if it is `None`
+    Pretty {
+        max_column_width: Option<usize>,
+    },
+    Csv,
+    Ndjson,
+}
 
-    let mut password = String::default();
-    print!("Enter password: ");
-    std::io::stdout().flush()?;
-    std::io::stdin()
-        .read_line(&mut password)
-        .context("Failed to read line")?;
+impl OutputFormat {
+    fn from_flags(as_csv: bool, ndjson: bool, wrapping: &WrappingArgs) -> Self {
+        if as_csv {
+            Self::Csv
+        } else if ndjson {
+            Self::Ndjson
+        } else {
+            Self::Pretty {
+                max_column_width: wrapping.max_column_width(),
+            }
+        }
+    }
+}
 
-    let mut database_name = String::default();
-    print!("Enter name of database: ");
-    std::io::stdout().flush()?;
-    std::io::stdin()
-        .read_line(&mut database_name)
-        .context("Failed to read line")?;
-
-    let config = Configuration {
-        database_name: database_name.trim().into(),
-        user: user.trim().into(),
-        password: password.trim().into(),
-        host: host.trim().into(),
-        port: port.trim().into(),
-    };
-    config
-        .store()
-        .context("Failed to store new configuration to disk")?;
-    config.apply();
-    Ok(())
+/// Default width at which the cells of pretty-printed tables are wrapped
+const DEFAULT_MAX_COLUMN_WIDTH: usize = 24;
+
+/// Flags that control how the cells of pretty-printed tables are wrapped
+#[derive(clap::Args, Debug)]
+struct WrappingArgs {
+    #[arg(
+        long,
+        default_value_t = DEFAULT_MAX_COLUMN_WIDTH,
+        help = "Wrap the cells of the printed table at this many characters (0 disables wrapping)"
+    )]
+    max_col_width: usize,
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "max_col_width",
+        help = "Don't wrap the cells of the printed table, e.g. to copy long values"
+    )]
+    no_wrap: bool,
+}
+
+impl WrappingArgs {
+    fn max_column_width(&self) -> Option<usize> {
+        if self.no_wrap || self.max_col_width == 0 {
+            None
+        } else {
+            Some(self.max_col_width)
+        }
+    }
 }
 
 struct GenericTable {
@@ -177,7 +702,17 @@ struct GenericTable {
 }
 
 impl GenericTable {
-    fn write_pretty<W: Write>(&self, mut writer: W) -> Result<()> {
+    fn write<W: Write>(&self, format: OutputFormat, writer: W) -> Result<()> {
+        match format {
+            OutputFormat::Pretty { max_column_width } => {
+                self.write_pretty(max_column_width, writer)
+            }
+            OutputFormat::Csv => self.write_csv(writer),
+            OutputFormat::Ndjson => self.write_ndjson(writer),
+        }
+    }
+
+    fn write_pretty<W: Write>(&self, max_column_width: Option<usize>, mut writer: W) -> Result<()> {
         let mut table_builder = Builder::default();
         table_builder.set_header(&self.header);
 
@@ -190,7 +725,9 @@ impl GenericTable {
         // let (terminal_width, _) =
         //     termion::terminal_size().context("Can't determine terminal size")?;
         // table.with(Width::wrap(terminal_width as usize));
-        table.with(Modify::new(Rows::new(..)).with(Width::wrap(24)));
+        if let Some(max_column_width) = max_column_width {
+            table.with(Modify::new(Rows::new(..)).with(Width::wrap(max_column_width)));
+        }
         table.with(Style::modern());
 
         write!(writer, "{table}")?;
@@ -200,7 +737,7 @@ impl GenericTable {
 
     fn write_csv<W: Write>(&self, mut writer: W) -> Result<()> {
         let cleanup_str_for_csv = |s: &String| -> String {
-            let must_be_quoted = s.contains(&['\n', '\r', ',']);
+            let must_be_quoted = s.contains(['\n', '\r', ',']);
             if !must_be_quoted {
                 s.to_owned()
             } else {
@@ -229,10 +766,58 @@ impl GenericTable {
         }
         Ok(())
     }
+
+    /// Writes one JSON object per row, with the column names as keys in the order of the header. Every line, including
+    /// the last one, is terminated by a newline so that the output can be appended to or streamed into other tools
+    fn write_ndjson<W: Write>(&self, mut writer: W) -> Result<()> {
+        for row in &self.rows {
+            let fields = self
+                .header
+                .iter()
+                .zip(row)
+                .map(|(key, value)| -> Result<String> {
+                    Ok(format!(
+                        "{}:{}",
+                        serde_json::to_string(key)?,
+                        serde_json::to_string(value)?
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?
+                .join(",");
+            writeln!(writer, "{{{fields}}}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes this table into the given worksheet. Cells that contain numbers are written as numbers, so that they can
+    /// be used in formulas and charts, all other cells are written as strings
+    #[cfg(feature = "xlsx")]
+    fn write_to_worksheet(&self, worksheet: &mut rust_xlsxwriter::Worksheet) -> Result<()> {
+        let header_format = rust_xlsxwriter::Format::new().set_bold();
+        for (column, name) in self.header.iter().enumerate() {
+            worksheet.write_string_with_format(0, column as u16, name, &header_format)?;
+        }
+        for (row_index, row) in self.rows.iter().enumerate() {
+            let row_index = row_index as u32 + 1;
+            for (column, cell) in row.iter().enumerate() {
+                match cell.parse::<f64>() {
+                    Ok(number) if number.is_finite() => {
+                        worksheet.write_number(row_index, column as u16, number)?
+                    }
+                    _ => worksheet.write_string(row_index, column as u16, cell)?,
+                };
+            }
+        }
+        Ok(())
+    }
 }
 
-fn list_experiments(as_csv: bool) -> Result<()> {
-    let all_experiments = Experiment::all().context("Error while fetching experiments")?;
+fn list_experiments(format: OutputFormat, tag: Option<&str>) -> Result<()> {
+    let all_experiments = match tag {
+        Some(tag) => Experiment::with_tag(tag),
+        None => Experiment::all(),
+    }
+    .context("Error while fetching experiments")?;
 
     const MAX_DESCRIPTION_LENGTH: usize = 32;
 
@@ -241,11 +826,12 @@ fn list_experiments(as_csv: bool) -> Result<()> {
         "researcher".into(),
         "description".into(),
         "variable_names".into(),
+        "runs".into(),
     ];
 
     let rows = all_experiments
         .iter()
-        .map(|ex| {
+        .map(|ex| -> Result<Vec<String>> {
             let description_short = if ex.description().len() > MAX_DESCRIPTION_LENGTH {
                 format!("{}...", &ex.description()[..MAX_DESCRIPTION_LENGTH])
             } else {
@@ -257,92 +843,246 @@ fn list_experiments(as_csv: bool) -> Result<()> {
                 .collect::<Vec<_>>()
                 .join(",");
 
-            vec![
+            let run_count = ex
+                .count_runs()
+                .with_context(|| format!("Failed to count runs of experiment {}", ex.name()))?;
+
+            Ok(vec![
                 ex.name().to_owned(),
                 ex.researcher().to_owned(),
                 description_short,
                 variable_names,
-            ]
+                run_count.to_string(),
+            ])
         })
-        .collect();
+        .collect::<Result<_>>()?;
 
     let generic_table = GenericTable { header, rows };
 
-    if as_csv {
-        generic_table.write_csv(std::io::stdout())?;
-    } else {
-        generic_table.write_pretty(std::io::stdout())?;
-    }
+    generic_table.write(format, std::io::stdout())?;
 
     Ok(())
 }
 
-fn list_runs(experiment_name: &str, as_csv: bool) -> Result<()> {
+fn list_runs(
+    experiment_name: &str,
+    format: OutputFormat,
+    oneline: bool,
+    limit: Option<usize>,
+    offset: usize,
+    predicates: &[MeasurementPredicate],
+    latest: bool,
+) -> Result<()> {
     let experiment = Experiment::from_name(experiment_name)
         .context("Failed to query database for experiments")?;
     match experiment {
-        None => bail!("No experiment with name {experiment_name} found"),
+        None => {
+            return Err(
+                NotFoundError(format!("No experiment with name {experiment_name} found")).into(),
+            )
+        }
         Some(experiment) => {
-            let all_runs = experiment
-                .all_runs()
-                .context("Failed to get runs for experiment")?;
-
-            let header = vec![
-                "run_number".to_owned(),
-                "run_id".to_owned(),
-                "timestamp".to_owned(),
-            ];
+            if latest {
+                match experiment
+                    .latest_run()
+                    .context("Failed to get latest run of experiment")?
+                {
+                    Some(run) => write_run(&run, format, oneline)?,
+                    None => eprintln!("Experiment {experiment_name} has no runs"),
+                }
+                return Ok(());
+            }
 
-            let rows = all_runs
-                .iter()
-                .map(|run| {
-                    let timestamp = run
-                        .measurements()
-                        .first()
-                        .map(|measurement| {
-                            let time_since_epoch = measurement
-                                .timestamp()
-                                .duration_since(SystemTime::UNIX_EPOCH)
-                                .expect("Failed to get time since epoch");
-
-                            NaiveDateTime::from_timestamp_millis(
-                                    time_since_epoch.as_millis() as i64
-                                )
-                                .map(|date_time| {
-                                    date_time.and_utc().with_timezone(&Local).to_string()
-                                })
-                                .unwrap_or("unknown".into())
-                        })
-                        .unwrap_or("unknown".into());
-                    vec![run.run_number().to_string(), run.id().to_owned(), timestamp]
-                })
-                .collect();
+            if !predicates.is_empty() {
+                // The predicates are evaluated by the database, so only the headers of matching runs are fetched
+                let matching_run_headers = experiment
+                    .run_headers_matching(predicates)
+                    .context("Failed to get runs for experiment")?
+                    .into_iter()
+                    .skip(offset)
+                    .take(limit.unwrap_or(usize::MAX))
+                    .collect::<Vec<_>>();
+                if oneline {
+                    let mut stdout = std::io::stdout();
+                    for run_header in &matching_run_headers {
+                        let run = Run::from_raw_run(run_header, &experiment)
+                            .context("Failed to get runs for experiment")?;
+                        writeln!(stdout, "{}", format_run_as_line(&run))?;
+                    }
+                } else {
+                    run_headers_table(&matching_run_headers).write(format, std::io::stdout())?;
+                }
+                return Ok(());
+            }
 
-            let generic_table = GenericTable { header, rows };
-            if as_csv {
-                generic_table.write_csv(std::io::stdout())?;
-            } else {
-                generic_table.write_pretty(std::io::stdout())?;
+            if oneline {
+                // The runs are streamed, so runs after the requested page are never fetched
+                let runs = experiment
+                    .stream_runs()
+                    .context("Failed to get runs for experiment")?
+                    .skip(offset)
+                    .take(limit.unwrap_or(usize::MAX));
+                let mut stdout = std::io::stdout();
+                for run in runs {
+                    let run = run.context("Failed to get runs for experiment")?;
+                    writeln!(stdout, "{}", format_run_as_line(&run))?;
+                }
+                return Ok(());
             }
+
+            // Listing the runs does not require any measurements, so only the run headers are fetched
+            let run_headers = experiment
+                .run_headers_page(limit, offset)
+                .context("Failed to get runs for experiment")?;
+            run_headers_table(&run_headers).write(format, std::io::stdout())?;
         }
     }
     Ok(())
 }
 
+/// Creates a table with the run number, ID, label and timestamp of the given runs
+fn run_headers_table(run_headers: &[RawRun]) -> GenericTable {
+    let header = vec![
+        "run_number".to_owned(),
+        "run_id".to_owned(),
+        "label".to_owned(),
+        "timestamp".to_owned(),
+    ];
+
+    let rows = run_headers
+        .iter()
+        .map(|run| {
+            let timestamp = to_date_time(run.timestamp)
+                .map(|date_time| date_time.with_timezone(&Local).to_string())
+                .unwrap_or("unknown".into());
+            vec![
+                run.run_number.to_string(),
+                run.run_id.clone(),
+                run.label.clone().unwrap_or_default(),
+                timestamp,
+            ]
+        })
+        .collect();
+
+    GenericTable { header, rows }
+}
+
 /// Format a variable value for printing. This removes newlines and carriage returns from the string
-/// so that it can be written as a single line of a table or CSV file
-fn format_variable_value(value: &str) -> String {
-    value.replace("\n", "").replace("\r", "")
+/// so that it can be written as a single line of a table or CSV file. Missing values are printed as "N/A"
+fn format_variable_value(value: Option<&str>) -> String {
+    match value {
+        Some(value) => value.replace(['\n', '\r'], ""),
+        None => "N/A".to_owned(),
+    }
+}
+
+/// Returns the point in time at which the given run was recorded
+fn run_timestamp(run: &Run) -> Option<DateTime<Utc>> {
+    to_date_time(run.timestamp())
+}
+
+fn to_date_time(timestamp: SystemTime) -> Option<DateTime<Utc>> {
+    let time_since_epoch = timestamp.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    DateTime::from_timestamp_millis(time_since_epoch.as_millis() as i64)
+}
+
+/// Quotes the given string if it would otherwise be ambiguous within a single-line run summary, i.e.
+/// if it is empty or contains whitespace, quotes, backslashes or an equals sign
+fn quote_for_line(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\\' || c == '=');
+    if !needs_quotes {
+        return value.to_owned();
+    }
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\"{escaped}\"")
+}
+
+/// Formats a run as a single line of the form `id date var1=val1 var2=val2 ...`. The date is in RFC 3339
+/// format (UTC), variables are in the order given, missing values are printed as `N/A`
+fn format_line(
+    run_id: &str,
+    timestamp: Option<DateTime<Utc>>,
+    values: &[(&str, Option<&str>)],
+) -> String {
+    let timestamp = timestamp
+        .map(|date_time| date_time.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or("unknown".into());
+    std::iter::once(quote_for_line(run_id))
+        .chain(std::iter::once(timestamp))
+        .chain(values.iter().map(|(name, value)| {
+            format!(
+                "{}={}",
+                quote_for_line(name),
+                value.map(quote_for_line).unwrap_or("N/A".to_owned())
+            )
+        }))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_run_as_line(run: &Run) -> String {
+    let values = run
+        .measurements()
+        .iter()
+        .map(|measurement| {
+            (
+                measurement.variable().template().name(),
+                measurement.value(),
+            )
+        })
+        .collect::<Vec<_>>();
+    format_line(run.id(), run_timestamp(run), &values)
+}
+
+/// Prints a single run. If `experiment_name` is given, `run` is the label of the run within this experiment, otherwise
+/// it is the run ID
+fn print_run(
+    run: &str,
+    experiment_name: Option<&str>,
+    format: OutputFormat,
+    oneline: bool,
+) -> Result<()> {
+    let experiment = match experiment_name {
+        Some(experiment_name) => Experiment::from_name(experiment_name)
+            .context("Failed to query database for experiments")?
+            .ok_or_else(|| {
+                NotFoundError(format!("No experiment with name {experiment_name} found"))
+            })?,
+        None => Experiment::from_run_id(run)
+            .context("Failed to fetch experiment for run ID")?
+            .ok_or_else(|| NotFoundError(format!("No experiment found for run ID {run}")))?,
+    };
+    let run = match experiment_name {
+        Some(experiment_name) => experiment
+            .run_from_label(run)
+            .context("Failed to fetch run from DB")?
+            .ok_or_else(|| {
+                NotFoundError(format!(
+                    "No run with label {run} found for experiment {experiment_name}"
+                ))
+            })?,
+        None => experiment
+            .run_from_id(run)
+            .context("Failed to fetch run from DB")?
+            .ok_or_else(|| NotFoundError(format!("No run found for run ID {run}")))?,
+    };
+
+    write_run(&run, format, oneline)
 }
 
-fn print_run(run_id: &str, as_csv: bool) -> Result<()> {
-    let experiment = Experiment::from_run_id(run_id)
-        .context("Failed to fetch experiment for run ID")?
-        .ok_or(anyhow!("No experiment found for run ID"))?;
-    let run = experiment
-        .run_from_id(run_id)
-        .context("Failed to fetch run from DB")?
-        .ok_or(anyhow!("No run found for run ID"))?;
+/// Writes a single run to stdout, either as a table with one column per variable or as a single line
+fn write_run(run: &Run, format: OutputFormat, oneline: bool) -> Result<()> {
+    if oneline {
+        writeln!(std::io::stdout(), "{}", format_run_as_line(run))?;
+        return Ok(());
+    }
 
     let header = std::iter::once(String::from("run_number")).chain(
         run.measurements()
@@ -361,74 +1101,518 @@ fn print_run(run_id: &str, as_csv: bool) -> Result<()> {
         rows: vec![row.collect()],
     };
 
-    if as_csv {
-        generic_table.write_csv(std::io::stdout())?;
+    generic_table.write(format, std::io::stdout())?;
+
+    Ok(())
+}
+
+/// Creates a table with one row per run of the given experiment and one column per variable, sorted by variable name
+fn all_runs_table(experiment: &Experiment) -> Result<GenericTable> {
+    // Fetching experiments with many runs can take a while, so report the progress if someone is watching
+    let show_progress = termion::is_tty(&std::io::stderr());
+    let run_count = if show_progress {
+        experiment
+            .count_runs()
+            .context("Failed to count runs of experiment")?
     } else {
-        generic_table.write_pretty(std::io::stdout())?;
+        0
+    };
+    let mut run_stream = experiment
+        .stream_runs()
+        .context("Failed to get runs for experiment")?;
+    if show_progress {
+        run_stream = run_stream.with_progress(move |runs| {
+            eprint!("\rFetched {runs} of {run_count} runs");
+        });
+    }
+    let all_runs = run_stream
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to get runs for experiment")?;
+    if show_progress {
+        eprintln!("\rFetched {0} of {0} runs", all_runs.len());
     }
 
+    let mut expected_variables = experiment.variables().collect::<Vec<_>>();
+    expected_variables.sort_by(|a, b| a.template().name().cmp(b.template().name()));
+
+    let header = std::iter::once(String::from("run_number"))
+        .chain(
+            expected_variables
+                .iter()
+                .map(|variable| variable.template().name().to_owned()),
+        )
+        .collect();
+
+    let rows = all_runs
+        .iter()
+        .map(|run| {
+            std::iter::once(run.run_number().to_string())
+                .chain(expected_variables.iter().map(|variable| {
+                    run.measurements()
+                        .iter()
+                        .find(|measurement| measurement.variable().id() == variable.id())
+                        .map(|measurement| format_variable_value(measurement.value()))
+                        .unwrap_or("N/A".to_owned())
+                }))
+                .collect()
+        })
+        .collect();
+
+    Ok(GenericTable { header, rows })
+}
+
+fn print_all_runs(experiment_name: &str, format: OutputFormat) -> Result<()> {
+    let experiment = Experiment::from_name(experiment_name)
+        .context("Failed to query database for experiments")?
+        .ok_or_else(|| NotFoundError(format!("No experiment with name {experiment_name} found")))?;
+
+    let table = all_runs_table(&experiment)?;
+    table.write(format, std::io::stdout())?;
+
     Ok(())
 }
 
-fn print_all_runs(experiment_name: &str, as_csv: bool) -> Result<()> {
+/// Creates a table with the number of numeric values, the number of missing values, and the minimum, maximum and mean
+/// of the numeric values for each variable in the given table of runs (see `all_runs_table`). `unit_of` returns the
+/// unit of a variable by its name. NaN and infinite values are counted separately and are not part of the minimum,
+/// maximum and mean. Variables without any finite values are skipped
+#[cfg(feature = "xlsx")]
+fn variable_statistics_table<F: Fn(&str) -> String>(
+    runs_table: &GenericTable,
+    unit_of: F,
+) -> GenericTable {
+    let header = [
+        "variable",
+        "unit",
+        "count",
+        "missing",
+        "non_finite",
+        "min",
+        "max",
+        "mean",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+
+    // The first column of the runs table is the run number, all other columns are variables
+    let rows = runs_table
+        .header
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter_map(|(column, variable_name)| {
+            let (values, non_finite_values): (Vec<f64>, Vec<f64>) = runs_table
+                .rows
+                .iter()
+                .filter_map(|row| row[column].parse::<f64>().ok())
+                .partition(|value| value.is_finite());
+            if values.is_empty() {
+                return None;
+            }
+            let missing = runs_table
+                .rows
+                .iter()
+                .filter(|row| row[column] == "N/A")
+                .count();
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            Some(vec![
+                variable_name.clone(),
+                unit_of(variable_name),
+                values.len().to_string(),
+                missing.to_string(),
+                non_finite_values.len().to_string(),
+                min.to_string(),
+                max.to_string(),
+                mean.to_string(),
+            ])
+        })
+        .collect();
+
+    GenericTable { header, rows }
+}
+
+/// Exports all runs of the given experiment into an Excel workbook with two sheets: 'Runs' with one row per run, and
+/// 'Statistics' with aggregated values for all numeric variables
+#[cfg(feature = "xlsx")]
+fn export_xlsx(experiment_name: &str, output: &std::path::Path) -> Result<()> {
     let experiment = Experiment::from_name(experiment_name)
-        .context("Failed to query database for experiments")?;
-    match experiment {
-        None => bail!("No experiment with name {experiment_name} found"),
-        Some(experiment) => {
-            let all_runs = experiment
-                .all_runs()
-                .context("Failed to get runs for experiment")?;
+        .context("Failed to query database for experiments")?
+        .ok_or_else(|| NotFoundError(format!("No experiment with name {experiment_name} found")))?;
 
-            let mut expected_variables = experiment.variables().collect::<Vec<_>>();
-            expected_variables.sort_by(|a, b| a.template().name().cmp(b.template().name()));
+    let runs_table = all_runs_table(&experiment)?;
+    if runs_table.rows.is_empty() {
+        bail!("Experiment {experiment_name} has no runs, there is nothing to export");
+    }
+    let statistics_table = variable_statistics_table(&runs_table, |variable_name| {
+        experiment
+            .variables()
+            .find(|variable| variable.template().name() == variable_name)
+            .map(|variable| variable.template().unit().to_owned())
+            .unwrap_or_default()
+    });
+
+    runs_workbook(&runs_table, &statistics_table)?
+        .save(output)
+        .context(format!("Failed to write workbook to {}", output.display()))?;
 
-            let header = std::iter::once(String::from("run_number"))
-                .chain(
-                    expected_variables
-                        .iter()
-                        .map(|variable| variable.template().name().to_owned()),
+    Ok(())
+}
+
+/// Creates an Excel workbook with the sheets 'Runs' and 'Statistics' from the given tables
+#[cfg(feature = "xlsx")]
+fn runs_workbook(
+    runs_table: &GenericTable,
+    statistics_table: &GenericTable,
+) -> Result<rust_xlsxwriter::Workbook> {
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    runs_table.write_to_worksheet(workbook.add_worksheet().set_name("Runs")?)?;
+    statistics_table.write_to_worksheet(workbook.add_worksheet().set_name("Statistics")?)?;
+    Ok(workbook)
+}
+
+/// Creates a Parquet column from the given values. The type of the column is inferred from the values: If all values
+/// are integers, the column is `Int64`, if all are numbers it is `Float64`, if all are `true` or `false` it is
+/// `Boolean`, otherwise it is `Utf8`. Missing values are stored as nulls
+#[cfg(feature = "parquet")]
+fn infer_parquet_column(values: &[Option<&str>]) -> arrow_array::ArrayRef {
+    use arrow_array::{BooleanArray, Float64Array, Int64Array, StringArray};
+    use std::sync::Arc;
+
+    fn parse_all<T: std::str::FromStr>(values: &[Option<&str>]) -> Option<Vec<Option<T>>> {
+        values
+            .iter()
+            .map(|value| match value {
+                None => Some(None),
+                Some(value) => value.trim().parse().ok().map(Some),
+            })
+            .collect()
+    }
+
+    if values.iter().all(Option::is_none) {
+        return Arc::new(StringArray::from(values.to_vec()));
+    }
+    if let Some(integers) = parse_all::<i64>(values) {
+        Arc::new(Int64Array::from(integers))
+    } else if let Some(numbers) = parse_all::<f64>(values) {
+        Arc::new(Float64Array::from(numbers))
+    } else if let Some(booleans) = parse_all::<bool>(values) {
+        Arc::new(BooleanArray::from(booleans))
+    } else {
+        Arc::new(StringArray::from(values.to_vec()))
+    }
+}
+
+/// Writes the given named columns as a single row group to a Parquet file
+#[cfg(feature = "parquet")]
+fn write_parquet<W: Write + Send>(
+    columns: Vec<(String, arrow_array::ArrayRef)>,
+    writer: W,
+) -> Result<()> {
+    let batch = arrow_array::RecordBatch::try_from_iter(columns)
+        .context("Failed to create record batch")?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Exports all runs of the given experiment into a Parquet file with one row per run. Besides one column per variable
+/// (see `infer_parquet_column` for their types), the file contains the `run_id`, `run_number`, `label` and `timestamp`
+/// of every run
+#[cfg(feature = "parquet")]
+fn export_parquet(experiment_name: &str, output: &std::path::Path) -> Result<()> {
+    use arrow_array::{Int64Array, StringArray, TimestampMillisecondArray};
+    use std::sync::Arc;
+
+    let experiment = Experiment::from_name(experiment_name)
+        .context("Failed to query database for experiments")?
+        .ok_or_else(|| NotFoundError(format!("No experiment with name {experiment_name} found")))?;
+
+    let runs = experiment
+        .stream_runs()
+        .context("Failed to get runs for experiment")?
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to get runs for experiment")?;
+    if runs.is_empty() {
+        bail!("Experiment {experiment_name} has no runs, there is nothing to export");
+    }
+
+    let mut variables = experiment.variables().collect::<Vec<_>>();
+    variables.sort_by(|a, b| a.template().name().cmp(b.template().name()));
+
+    let mut columns: Vec<(String, arrow_array::ArrayRef)> = vec![
+        (
+            "run_id".to_owned(),
+            Arc::new(StringArray::from_iter_values(
+                runs.iter().map(|run| run.id()),
+            )),
+        ),
+        (
+            "run_number".to_owned(),
+            Arc::new(Int64Array::from_iter_values(
+                runs.iter().map(|run| run.run_number() as i64),
+            )),
+        ),
+        (
+            "label".to_owned(),
+            Arc::new(StringArray::from(
+                runs.iter().map(|run| run.label()).collect::<Vec<_>>(),
+            )),
+        ),
+        (
+            "timestamp".to_owned(),
+            Arc::new(
+                TimestampMillisecondArray::from_iter_values(
+                    runs.iter()
+                        .map(|run| DateTime::<Utc>::from(run.timestamp()).timestamp_millis()),
                 )
-                .collect();
+                .with_timezone("UTC"),
+            ),
+        ),
+    ];
+    for variable in variables {
+        let values = runs
+            .iter()
+            .map(|run| {
+                run.measurements()
+                    .iter()
+                    .find(|measurement| measurement.variable().id() == variable.id())
+                    .and_then(|measurement| measurement.value())
+            })
+            .collect::<Vec<_>>();
+        columns.push((
+            variable.template().name().to_owned(),
+            infer_parquet_column(&values),
+        ));
+    }
 
-            let rows = all_runs
-                .iter()
-                .map(|run| {
-                    std::iter::once(run.run_number().to_string())
-                        .chain(expected_variables.iter().map(|variable| {
-                            run.measurements()
-                                .iter()
-                                .find(|measurement| measurement.variable().id() == variable.id())
-                                .map(|measurement| format_variable_value(measurement.value()))
-                                .unwrap_or("N/A".to_owned())
-                        }))
-                        .collect()
-                })
-                .collect();
+    let file = std::fs::File::create(output)
+        .with_context(|| format!("Failed to create file {}", output.display()))?;
+    write_parquet(columns, file)
+        .with_context(|| format!("Failed to write Parquet file {}", output.display()))
+}
 
-            let table = GenericTable { header, rows };
-            if as_csv {
-                table.write_csv(std::io::stdout())?;
-            } else {
-                table.write_pretty(std::io::stdout())?;
+/// Formats the values of the latest run of an experiment in the Prometheus text exposition format. Numeric values are
+/// exposed as gauges, `true` and `false` as 1 and 0. All other values can't be represented as metrics and are skipped
+#[cfg(feature = "metrics")]
+fn format_prometheus_metrics(
+    experiment_name: &str,
+    run_number: usize,
+    values: &[(&str, Option<&str>)],
+) -> String {
+    let escape_label = |label: &str| {
+        label
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    };
+    let experiment_name = escape_label(experiment_name);
+
+    let mut metrics = format!(
+        "# HELP exar_latest_run_number Run number of the latest run of the experiment\n\
+         # TYPE exar_latest_run_number gauge\n\
+         exar_latest_run_number{{experiment=\"{experiment_name}\"}} {run_number}\n\
+         # HELP exar_measurement Value of a variable in the latest run of the experiment\n\
+         # TYPE exar_measurement gauge\n"
+    );
+    for (variable, value) in values {
+        let value = match value.map(str::trim) {
+            Some("true") => 1.0,
+            Some("false") => 0.0,
+            Some(value) => match value.parse::<f64>() {
+                Ok(number) if number.is_finite() => number,
+                _ => continue,
+            },
+            None => continue,
+        };
+        metrics += &format!(
+            "exar_measurement{{experiment=\"{experiment_name}\",variable=\"{}\"}} {value}\n",
+            escape_label(variable)
+        );
+    }
+    metrics
+}
+
+/// Fetches the latest run of the given experiment and formats its values as Prometheus metrics
+#[cfg(feature = "metrics")]
+fn latest_run_metrics(experiment: &Experiment) -> Result<String> {
+    let Some(latest_run) = experiment
+        .latest_run()
+        .context("Failed to fetch latest run of experiment")?
+    else {
+        return Ok(format_prometheus_metrics(experiment.name(), 0, &[]));
+    };
+    let values = latest_run
+        .measurements()
+        .iter()
+        .map(|measurement| {
+            (
+                measurement.variable().template().name(),
+                measurement.value(),
+            )
+        })
+        .collect::<Vec<_>>();
+    Ok(format_prometheus_metrics(
+        experiment.name(),
+        latest_run.run_number(),
+        &values,
+    ))
+}
+
+/// How long `serve-metrics` waits for a client to send its request or to receive the response. Requests are answered
+/// one after another, so a client that connects but never sends a request must not block later scrapes
+#[cfg(feature = "metrics")]
+const METRICS_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Answers a single HTTP request with the current metrics if `/metrics` is requested, and with 404 otherwise. Fails if
+/// reading the request or writing the response takes longer than `timeout`
+#[cfg(feature = "metrics")]
+fn answer_metrics_request(
+    stream: std::net::TcpStream,
+    metrics: &str,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut reader = std::io::BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the request headers, the request itself is all we need
+    let mut header_line = String::new();
+    while reader.read_line(&mut header_line)? > 2 {
+        header_line.clear();
+    }
+
+    let (status, content_type, body) = match request_line.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics),
+        _ => ("404 Not Found", "text/plain", "Not found\n"),
+    };
+    write!(
+        &stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+/// Serves the values of the latest run of the given experiment as Prometheus metrics until the process is terminated.
+/// The metrics are fetched from the database every `interval` in the background, so scrapes never wait for the
+/// database
+#[cfg(feature = "metrics")]
+fn serve_metrics(
+    experiment_name: &str,
+    address: std::net::SocketAddr,
+    interval: std::time::Duration,
+) -> Result<()> {
+    let experiment = Experiment::from_name(experiment_name)
+        .context("Failed to query database for experiments")?
+        .ok_or_else(|| NotFoundError(format!("No experiment with name {experiment_name} found")))?;
+
+    let metrics = std::sync::Mutex::new(latest_run_metrics(&experiment)?);
+    let listener = std::net::TcpListener::bind(address)
+        .with_context(|| format!("Failed to listen on {address}"))?;
+    eprintln!("Serving metrics of experiment {experiment_name} at http://{address}/metrics");
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| loop {
+            std::thread::sleep(interval);
+            match latest_run_metrics(&experiment) {
+                Ok(latest_metrics) => *metrics.lock().expect("Lock was poisoned") = latest_metrics,
+                Err(why) => {
+                    eprintln!("Failed to fetch latest run, serving previous metrics: {why:?}")
+                }
+            }
+        });
+
+        for stream in listener.incoming() {
+            let current_metrics = metrics.lock().expect("Lock was poisoned").clone();
+            if let Err(why) = stream.map_err(anyhow::Error::from).and_then(|stream| {
+                answer_metrics_request(stream, &current_metrics, METRICS_REQUEST_TIMEOUT)
+            }) {
+                eprintln!("Failed to answer metrics request: {why:?}");
             }
         }
+        Ok(())
+    })
+}
+
+/// Prints which connection settings are set, and checks whether the database is reachable and has the required schema.
+/// Returns an error if any check fails
+fn doctor() -> Result<()> {
+    println!("Connection settings:");
+    for (var, is_secret) in [
+        ("PSQL_HOST", false),
+        ("PSQL_PORT", false),
+        ("PSQL_USER", false),
+        ("PSQL_PWD", true),
+        ("PSQL_DBNAME", false),
+        ("PSQL_SSLMODE", false),
+        ("PSQL_SSLROOTCERT", false),
+        ("PSQL_DRY_RUN", false),
+    ] {
+        let value = match std::env::var(var) {
+            Ok(_) if is_secret => "(set)".to_owned(),
+            Ok(value) => value,
+            Err(_) => "(not set)".to_owned(),
+        };
+        println!("  {var:<16} {value}");
+    }
+
+    if let Err(why) = ping() {
+        println!("Connection: FAIL");
+        return Err(why.context("Database is not reachable"));
+    }
+    println!("Connection: OK");
+
+    let missing_columns =
+        missing_schema_columns().context("Failed to check the database schema")?;
+    if !missing_columns.is_empty() {
+        println!("Schema: FAIL (missing {})", missing_columns.join(", "));
+        bail!("The database schema is incomplete. Create it from test_data/dbschema.sql, or run the migrations (e.g. add_run_label_column) for databases created with an older schema");
     }
+    println!("Schema: OK");
+
+    Ok(())
+}
+
+fn print_stats(format: OutputFormat) -> Result<()> {
+    let stats = GlobalStats::fetch().context("Failed to fetch statistics from database")?;
+
+    let header = vec![
+        "experiments".to_owned(),
+        "variables".to_owned(),
+        "runs".to_owned(),
+        "measurements".to_owned(),
+    ];
+    let rows = vec![vec![
+        stats.experiments.to_string(),
+        stats.variables.to_string(),
+        stats.runs.to_string(),
+        stats.measurements.to_string(),
+    ]];
+
+    let generic_table = GenericTable { header, rows };
+    generic_table.write(format, std::io::stdout())?;
 
     Ok(())
 }
 
-fn delete_experiment(experiment_name: &str) -> Result<()> {
+fn delete_experiment(experiment_name: &str, skip_confirmation: bool) -> Result<()> {
     let experiment = Experiment::from_name(experiment_name)
         .context("Failed to query database for experiments")?
-        .ok_or(anyhow!(
-            "No experiment with name \"{experiment_name}\" found"
-        ))?;
-
-    println!("Are you sure you want to delete all data for experiment \"{experiment_name}\"? This operation is not reversible! (y/n)");
-    let mut input = String::default();
-    std::io::stdin().read_line(&mut input)?;
-    if input.trim() != "y" {
+        .ok_or_else(|| {
+            NotFoundError(format!(
+                "No experiment with name \"{experiment_name}\" found"
+            ))
+        })?;
+
+    let run_count = experiment
+        .count_runs()
+        .context("Failed to count runs of experiment")?;
+    if !confirm(&format!("Are you sure you want to delete all data for experiment \"{experiment_name}\" ({run_count} run(s))? This operation is not reversible!"), skip_confirmation)? {
         return Ok(());
     }
 
@@ -437,6 +1621,90 @@ fn delete_experiment(experiment_name: &str) -> Result<()> {
     Ok(())
 }
 
+fn rename_experiment(old_name: &str, new_name: &str) -> Result<()> {
+    let mut experiment = Experiment::from_name(old_name)
+        .context("Failed to query database for experiments")?
+        .ok_or_else(|| NotFoundError(format!("No experiment with name \"{old_name}\" found")))?;
+
+    experiment.rename(new_name)
+}
+
+fn replay(fallback_file: &std::path::Path) -> Result<()> {
+    let run_ids = replay_fallback_file(fallback_file)?;
+    println!("Inserted {} run(s) into the database", run_ids.len());
+    Ok(())
+}
+
+fn archive_experiment(experiment_name: &str, output: Option<&std::path::Path>) -> Result<()> {
+    let experiment = Experiment::from_name(experiment_name)
+        .context("Failed to query database for experiments")?
+        .ok_or_else(|| NotFoundError(format!("No experiment with name {experiment_name} found")))?;
+    let archive = experiment.to_archive()?;
+
+    match output {
+        Some(output) => {
+            let file = std::fs::File::create(output)
+                .context(format!("Could not create file {}", output.display()))?;
+            serde_json::to_writer_pretty(std::io::BufWriter::new(file), &archive)
+                .context("Failed to write experiment to file")?;
+        }
+        None => {
+            serde_json::to_writer_pretty(std::io::stdout(), &archive)
+                .context("Failed to write experiment to stdout")?;
+            println!();
+        }
+    }
+    Ok(())
+}
+
+fn print_json_schema(experiment_name: &str) -> Result<()> {
+    let experiment = Experiment::from_name(experiment_name)
+        .context("Failed to query database for experiments")?
+        .ok_or_else(|| NotFoundError(format!("No experiment with name {experiment_name} found")))?;
+    serde_json::to_writer_pretty(std::io::stdout(), &experiment.to_json_schema())
+        .context("Failed to write schema to stdout")?;
+    println!();
+    Ok(())
+}
+
+fn import_experiment(file: &std::path::Path) -> Result<()> {
+    let file_contents =
+        std::fs::read_to_string(file).context(format!("Could not read file {}", file.display()))?;
+    let archive: ExperimentArchive =
+        serde_json::from_str(&file_contents).context("File does not contain a valid experiment")?;
+    let experiment = Experiment::import_archive(&archive)?;
+    println!(
+        "Imported experiment {} with {} run(s)",
+        experiment.name(),
+        archive.runs.len()
+    );
+    Ok(())
+}
+
+/// Parses an age such as `30d` or `12h` into a duration. The number must be followed by one of the units `s` (seconds),
+/// `m` (minutes), `h` (hours), `d` (days) or `w` (weeks)
+fn parse_age(age: &str) -> Result<std::time::Duration> {
+    let age = age.trim();
+    let Some(unit) = age.chars().last() else {
+        bail!("Age must not be empty");
+    };
+    let seconds_per_unit = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => bail!("Age \"{age}\" must end with one of the units s, m, h, d or w"),
+    };
+    let count = age[..age.len() - 1]
+        .parse::<u64>()
+        .with_context(|| format!("Invalid number in age \"{age}\""))?;
+    let seconds = count
+        .checked_mul(seconds_per_unit)
+        .ok_or_else(|| anyhow!("Age \"{age}\" is too large"))?;
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
 fn parse_run_numbers_to_vec(run_numbers: &str) -> Result<Vec<usize>> {
     // Either `run_numbers` is a single number, or a list of numbers (which contains at least one comma), or
     // a range of numbers (which contains exactly one dash)
@@ -472,20 +1740,19 @@ fn parse_run_numbers_to_vec(run_numbers: &str) -> Result<Vec<usize>> {
     }
 }
 
-fn delete_runs(experiment_name: &str, run_numbers: &str) -> Result<()> {
-    let run_numbers_vec =
-        parse_run_numbers_to_vec(run_numbers).context("Failed to parse run numbers")?;
+fn delete_runs(experiment_name: &str, run_numbers: &str, skip_confirmation: bool) -> Result<()> {
+    let run_numbers_vec = parse_run_numbers_to_vec(run_numbers)
+        .map_err(|why| UsageError(format!("Failed to parse run numbers: {why:#}")))?;
 
     let experiment = Experiment::from_name(experiment_name)
         .context("Failed to query database for experiments")?
-        .ok_or(anyhow!(
-            "No experiment with name \"{experiment_name}\" found"
-        ))?;
-
-    println!("Are you sure you want to delete run(s) {run_numbers} for experiment \"{experiment_name}\"? This operation is not reversible! (y/n)");
-    let mut input = String::default();
-    std::io::stdin().read_line(&mut input)?;
-    if input.trim() != "y" {
+        .ok_or_else(|| {
+            NotFoundError(format!(
+                "No experiment with name \"{experiment_name}\" found"
+            ))
+        })?;
+
+    if !confirm(&format!("Are you sure you want to delete run(s) {run_numbers} for experiment \"{experiment_name}\"? This operation is not reversible!"), skip_confirmation)? {
         return Ok(());
     }
 
@@ -496,48 +1763,535 @@ fn delete_runs(experiment_name: &str, run_numbers: &str) -> Result<()> {
     Ok(())
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn prune_runs(
+    experiment_name: &str,
+    older_than: std::time::Duration,
+    skip_confirmation: bool,
+) -> Result<()> {
+    let experiment = Experiment::from_name(experiment_name)
+        .context("Failed to query database for experiments")?
+        .ok_or_else(|| {
+            NotFoundError(format!(
+                "No experiment with name \"{experiment_name}\" found"
+            ))
+        })?;
+    // Runs can't be older than the epoch, so larger ages simply delete all runs
+    let cutoff = SystemTime::now()
+        .checked_sub(older_than)
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .max(SystemTime::UNIX_EPOCH);
+
+    if !confirm(&format!("Are you sure you want to delete all runs of experiment \"{experiment_name}\" from before {}? This operation is not reversible!", DateTime::<Utc>::from(cutoff).to_rfc3339_opts(SecondsFormat::Secs, true)), skip_confirmation)? {
+        return Ok(());
+    }
+
+    let deleted_runs = experiment
+        .delete_runs_in_date_range(SystemTime::UNIX_EPOCH..cutoff)
+        .context("Failed to delete runs")?;
+    println!("Deleted {deleted_runs} run(s)");
+
+    Ok(())
+}
+
+/// Sets up the connection parameters for the database. If all connection flags are given, they are used directly and
+/// no configuration file is read or written. Otherwise, the configuration file is loaded (or created interactively, if
+/// it does not exist and the connection environment variables are not set), and individual flags override its values
+fn setup_connection(args: &Args) -> Result<()> {
+    if let Some(configuration) = args.connection_configuration() {
+        configuration.apply();
+        return Ok(());
+    }
 
-    if load_config().context("Could not load configuration")? == false {
+    if !load_config().context("Could not load configuration")? && !connection_env_vars_are_set() {
         configure().context("Error while configuring default parameters")?;
     }
+    args.apply_connection_flags();
+    Ok(())
+}
+
+/// Writes the completion script for the given shell
+fn write_completions<W: Write>(shell: clap_complete::Shell, mut writer: W) {
+    clap_complete::generate(shell, &mut Args::command(), "phd-ex-cli", &mut writer);
+}
+
+fn run_command(args: &Args) -> Result<()> {
+    // Generating shell completions does not require a database connection
+    if !matches!(args.command, Commands::Completions { .. }) {
+        setup_connection(args)?;
+    }
 
     match &args.command {
         Commands::Configure {} => {
             configure().context("Error while configuring default parameters")?
         }
-        Commands::ListExperiments { as_csv } => {
-            list_experiments(*as_csv).context("Failed to list experiments")?
-        }
+        Commands::Doctor {} => doctor()?,
+        Commands::Completions { shell } => write_completions(*shell, std::io::stdout()),
+        Commands::ListExperiments {
+            as_csv,
+            ndjson,
+            wrapping,
+            tag,
+        } => list_experiments(
+            OutputFormat::from_flags(*as_csv, *ndjson, wrapping),
+            tag.as_deref(),
+        )
+        .context("Failed to list experiments")?,
         Commands::ListRuns {
             experiment_name,
             as_csv,
-        } => list_runs(&experiment_name, *as_csv).context("Failed to list runs for experiment")?,
-        Commands::PrintRun { run_id, as_csv } => {
-            print_run(&run_id, *as_csv).context("Failed to print run")?
-        }
+            ndjson,
+            wrapping,
+            oneline,
+            limit,
+            offset,
+            predicates,
+            latest,
+        } => list_runs(
+            experiment_name,
+            OutputFormat::from_flags(*as_csv, *ndjson, wrapping),
+            *oneline,
+            *limit,
+            *offset,
+            predicates,
+            *latest,
+        )
+        .context("Failed to list runs for experiment")?,
+        Commands::PrintRun {
+            run,
+            experiment,
+            as_csv,
+            ndjson,
+            wrapping,
+            oneline,
+        } => print_run(
+            run,
+            experiment.as_deref(),
+            OutputFormat::from_flags(*as_csv, *ndjson, wrapping),
+            *oneline,
+        )
+        .context("Failed to print run")?,
         Commands::PrintAllRuns {
             experiment_name,
             as_csv,
-        } => print_all_runs(experiment_name, *as_csv)
-            .context("Failed to print all runs of experiment")?,
-        Commands::DeleteExperiment { experiment_name } => {
-            delete_experiment(&experiment_name).context("Failed to delete experiment")?
+            ndjson,
+            wrapping,
+        } => print_all_runs(
+            experiment_name,
+            OutputFormat::from_flags(*as_csv, *ndjson, wrapping),
+        )
+        .context("Failed to print all runs of experiment")?,
+        #[cfg(feature = "xlsx")]
+        Commands::Export {
+            experiment_name,
+            output,
+        } => export_xlsx(experiment_name, output).context("Failed to export experiment")?,
+        #[cfg(feature = "parquet")]
+        Commands::ExportParquet {
+            experiment_name,
+            output,
+        } => export_parquet(experiment_name, output).context("Failed to export experiment")?,
+        #[cfg(feature = "metrics")]
+        Commands::ServeMetrics {
+            experiment_name,
+            metrics_port,
+            bind_address,
+            interval,
+        } => serve_metrics(
+            experiment_name,
+            (*bind_address, *metrics_port).into(),
+            std::time::Duration::from_secs(*interval),
+        )
+        .context("Failed to serve metrics")?,
+        Commands::Stats {
+            as_csv,
+            ndjson,
+            wrapping,
+        } => print_stats(OutputFormat::from_flags(*as_csv, *ndjson, wrapping))
+            .context("Failed to print database statistics")?,
+        Commands::DeleteExperiment {
+            experiment_name,
+            yes,
+        } => delete_experiment(experiment_name, *yes).context("Failed to delete experiment")?,
+        Commands::Rename { old_name, new_name } => {
+            rename_experiment(old_name, new_name).context("Failed to rename experiment")?
+        }
+        Commands::Replay { fallback_file } => {
+            replay(fallback_file).context("Failed to replay fallback file")?
+        }
+        Commands::Archive {
+            experiment_name,
+            output,
+        } => archive_experiment(experiment_name, output.as_deref())
+            .context("Failed to archive experiment")?,
+        Commands::Schema { experiment_name } => {
+            print_json_schema(experiment_name).context("Failed to print schema of experiment")?
+        }
+        Commands::Import { file } => {
+            import_experiment(file).context("Failed to import experiment")?
         }
         Commands::DeleteRuns {
             experiment_name,
             run_numbers,
-        } => delete_runs(&experiment_name, &run_numbers).context("Failed to delete runs")?,
+            yes,
+        } => delete_runs(experiment_name, run_numbers, *yes).context("Failed to delete runs")?,
+        Commands::Prune {
+            experiment_name,
+            older_than,
+            yes,
+        } => prune_runs(experiment_name, *older_than, *yes).context("Failed to prune runs")?,
     }
 
     Ok(())
 }
 
+fn main() {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(why) => {
+            // Help and version output are also reported as errors by clap, but these are not failures
+            let exit_code = if why.use_stderr() {
+                EXIT_USAGE
+            } else {
+                EXIT_SUCCESS
+            };
+            let _ = why.print();
+            std::process::exit(exit_code);
+        }
+    };
+
+    if let Err(why) = run_command(&args) {
+        eprintln!("Error: {why:?}");
+        std::process::exit(exit_code_for_error(&why));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn run_line_format() {
+        let timestamp = DateTime::from_timestamp_millis(1_700_000_000_123).unwrap();
+        assert_eq!(
+            "abc123 2023-11-14T22:13:20.123Z runtime=12.5 host=node1",
+            format_line(
+                "abc123",
+                Some(timestamp),
+                &[("runtime", Some("12.5")), ("host", Some("node1"))]
+            )
+        );
+
+        // Values with whitespace, quotes or '=' are quoted and escaped, missing values become N/A
+        assert_eq!(
+            r#"abc123 unknown "the comment"="a \"b\" c" empty="" k="x=y" missing=N/A"#,
+            format_line(
+                "abc123",
+                None,
+                &[
+                    ("the comment", Some(r#"a "b" c"#)),
+                    ("empty", Some("")),
+                    ("k", Some("x=y")),
+                    ("missing", None)
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn run_line_uses_run_timestamp() {
+        // The timestamp is the one of the run itself, so it is the same as in the table of `list-runs` and is known
+        // even if the run has no measurements
+        let timestamp =
+            SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_123);
+        let run = Run::new("abc123".to_owned(), 1, None, timestamp, vec![]);
+        assert_eq!("abc123 2023-11-14T22:13:20.123Z", format_run_as_line(&run));
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn statistics_sheet() {
+        let runs_table = GenericTable {
+            header: ["run_number", "runtime", "host", "size"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            rows: [
+                ["1", "1.5", "node1", "NaN"],
+                ["2", "2.5", "node2", "inf"],
+                ["3", "N/A", "node1", "-inf"],
+                ["4", "inf", "node2", "N/A"],
+            ]
+            .into_iter()
+            .map(|row| row.into_iter().map(String::from).collect())
+            .collect(),
+        };
+
+        // NaN and infinite values are counted, but must not affect min, max and mean. Variables without any finite
+        // values (host and size) are skipped
+        let statistics_table = variable_statistics_table(&runs_table, |variable_name| {
+            if variable_name == "runtime" {
+                "s".to_owned()
+            } else {
+                String::new()
+            }
+        });
+        assert_eq!(
+            vec![
+                "variable",
+                "unit",
+                "count",
+                "missing",
+                "non_finite",
+                "min",
+                "max",
+                "mean"
+            ],
+            statistics_table.header
+        );
+        assert_eq!(
+            vec![vec!["runtime", "s", "2", "1", "1", "1.5", "2.5", "2"]],
+            statistics_table.rows
+        );
+
+        let workbook = runs_workbook(&runs_table, &statistics_table)
+            .unwrap()
+            .save_to_buffer()
+            .unwrap();
+        // Workbooks are zip archives
+        assert!(workbook.starts_with(b"PK"));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn silent_metrics_client_times_out() {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let _client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let start = std::time::Instant::now();
+        let result = answer_metrics_request(stream, "", std::time::Duration::from_millis(100));
+        assert!(result.is_err());
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn prometheus_metrics_format() {
+        let metrics = format_prometheus_metrics(
+            r#"exp "1""#,
+            3,
+            &[
+                ("runtime", Some("12.5")),
+                ("converged", Some("true")),
+                ("host", Some("node1")),
+                ("missing", None),
+            ],
+        );
+        let samples = metrics
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                r#"exar_latest_run_number{experiment="exp \"1\""} 3"#,
+                r#"exar_measurement{experiment="exp \"1\"",variable="runtime"} 12.5"#,
+                r#"exar_measurement{experiment="exp \"1\"",variable="converged"} 1"#,
+            ],
+            samples
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_columns_are_typed() {
+        use arrow_schema::DataType;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let columns = [
+            ("integers", vec![Some("1"), Some(" 2"), None]),
+            ("numbers", vec![Some("1"), Some("2.5"), Some("-3e2")]),
+            ("booleans", vec![Some("true"), None, Some("false")]),
+            ("strings", vec![Some("a"), Some("1"), Some("true")]),
+            ("missing", vec![None, None, None]),
+        ];
+        let path = std::env::temp_dir().join(format!(
+            "parquet_columns_are_typed_{}.parquet",
+            std::process::id()
+        ));
+        write_parquet(
+            columns
+                .iter()
+                .map(|(name, values)| (name.to_string(), infer_parquet_column(values)))
+                .collect(),
+            std::fs::File::create(&path).unwrap(),
+        )
+        .unwrap();
+
+        let reader =
+            ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap();
+        let data_types = reader
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| (field.name().clone(), field.data_type().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("integers".to_owned(), DataType::Int64),
+                ("numbers".to_owned(), DataType::Float64),
+                ("booleans".to_owned(), DataType::Boolean),
+                ("strings".to_owned(), DataType::Utf8),
+                ("missing".to_owned(), DataType::Utf8),
+            ],
+            data_types
+        );
+        let row_count = reader
+            .build()
+            .unwrap()
+            .map(|batch| batch.unwrap().num_rows())
+            .sum::<usize>();
+        assert_eq!(3, row_count);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn encrypted_password_round_trip() {
+        let encrypted = encrypt_password("p@ss, word", "passphrase").unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_PASSWORD_PREFIX));
+        assert!(!encrypted.contains("p@ss"));
+        assert_eq!(
+            "p@ss, word",
+            decrypt_password(&encrypted, "passphrase").unwrap()
+        );
+        // Every encryption uses a new salt and nonce
+        assert_ne!(
+            encrypted,
+            encrypt_password("p@ss, word", "passphrase").unwrap()
+        );
+
+        assert!(decrypt_password(&encrypted, "wrong passphrase").is_err());
+        assert!(decrypt_password("p@ss, word", "passphrase").is_err());
+        assert!(
+            decrypt_password(&format!("{ENCRYPTED_PASSWORD_PREFIX}AAAA"), "passphrase").is_err()
+        );
+
+        let mut config = Configuration {
+            user: "user".into(),
+            password: encrypted,
+            host: "localhost".into(),
+            port: "5432".into(),
+            database_name: "db".into(),
+        };
+        assert!(config.decrypt_stored_password(None).is_err());
+        assert!(!config.decrypt_stored_password(Some("passphrase")).unwrap());
+        assert_eq!("p@ss, word", config.password);
+        // Plaintext passwords must be encrypted once a passphrase is set
+        assert!(config.decrypt_stored_password(Some("passphrase")).unwrap());
+        assert!(!config.decrypt_stored_password(None).unwrap());
+    }
+
+    #[test]
+    fn bash_completions() {
+        let mut output = Vec::new();
+        write_completions(clap_complete::Shell::Bash, &mut output);
+        let output = String::from_utf8(output).unwrap();
+        for subcommand in ["list-experiments", "list-runs", "print-run", "completions"] {
+            assert!(
+                output.contains(subcommand),
+                "Missing subcommand {subcommand}"
+            );
+        }
+    }
+
+    #[test]
+    fn ndjson_format() {
+        let table = GenericTable {
+            header: vec!["name".into(), "comment".into()],
+            rows: vec![
+                vec!["a".into(), r#"say "hi", then leave"#.into()],
+                vec!["b".into(), "N/A".into()],
+            ],
+        };
+        let mut output = Vec::new();
+        table.write_ndjson(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"name":"a","comment":"say \"hi\", then leave"}"#,
+                "\n",
+                r#"{"name":"b","comment":"N/A"}"#,
+                "\n"
+            ),
+            output
+        );
+        for line in output.lines() {
+            assert!(serde_json::from_str::<serde_json::Value>(line).is_ok());
+        }
+    }
+
+    #[test]
+    fn pretty_format_wrapping() {
+        let long_value = "x".repeat(40);
+        let table = GenericTable {
+            header: vec!["name".into(), "value".into()],
+            rows: vec![vec!["a".into(), long_value.clone()]],
+        };
+        let render = |max_column_width| {
+            let mut output = Vec::new();
+            table.write_pretty(max_column_width, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let wrapped = render(Some(10));
+        assert!(!wrapped.contains(&long_value));
+        assert!(wrapped.contains(&"x".repeat(10)));
+        assert!(!wrapped.contains(&"x".repeat(11)));
+
+        let unwrapped = render(None);
+        assert!(unwrapped.contains(&long_value));
+        assert_eq!(5, unwrapped.lines().count());
+
+        let args = Args::try_parse_from(["phd-ex-cli", "list-experiments", "--no-wrap"]).unwrap();
+        let Commands::ListExperiments { wrapping, .. } = &args.command else {
+            panic!("Expected list-experiments command");
+        };
+        assert_eq!(None, wrapping.max_column_width());
+        assert!(Args::try_parse_from([
+            "phd-ex-cli",
+            "list-experiments",
+            "--no-wrap",
+            "--max-col-width",
+            "10"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn parse_ages() {
+        use std::time::Duration;
+
+        assert_eq!(Duration::from_secs(45), parse_age("45s").unwrap());
+        assert_eq!(Duration::from_secs(90 * 60), parse_age("90m").unwrap());
+        assert_eq!(Duration::from_secs(12 * 60 * 60), parse_age("12h").unwrap());
+        assert_eq!(
+            Duration::from_secs(30 * 24 * 60 * 60),
+            parse_age(" 30d ").unwrap()
+        );
+        assert_eq!(
+            Duration::from_secs(2 * 7 * 24 * 60 * 60),
+            parse_age("2w").unwrap()
+        );
+
+        assert!(parse_age("").is_err());
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("-1d").is_err());
+        assert!(parse_age("1.5d").is_err());
+        assert!(parse_age("30y").is_err());
+        assert!(parse_age(&format!("{}w", u64::MAX)).is_err());
+    }
+
     #[test]
     fn parse_run_numbers() {
         // Nothing
@@ -588,4 +2342,87 @@ mod tests {
         assert!(parse_run_numbers_to_vec("1-").is_err());
         assert!(parse_run_numbers_to_vec("-").is_err());
     }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(
+            EXIT_FAILURE,
+            exit_code_for_error(&anyhow::anyhow!("Something went wrong"))
+        );
+        assert_eq!(
+            EXIT_NOT_FOUND,
+            exit_code_for_error(
+                &anyhow::Error::from(NotFoundError("Not found".into())).context("Command failed")
+            )
+        );
+        assert_eq!(
+            EXIT_NOT_FOUND,
+            exit_code_for_error(
+                &anyhow::Error::from(ExarError::ExperimentNotFound("name".into()))
+                    .context("Command failed")
+            )
+        );
+
+        // Malformed run numbers are detected before connecting to the database
+        let res = delete_runs("some experiment", "1-", true);
+        assert!(res.is_err());
+        assert_eq!(EXIT_USAGE, exit_code_for_error(&res.unwrap_err()));
+        // The exit code for failed connections is tested in tests/cli.rs, which runs the CLI with its own environment
+    }
+
+    #[test]
+    fn connection_flags() {
+        let args = Args::try_parse_from([
+            "phd-ex-cli",
+            "list-experiments",
+            "--host",
+            "db.example.com",
+            "--port",
+            "5433",
+            "--user",
+            "ci",
+            "--password",
+            "secret",
+            "--dbname",
+            "experiments",
+        ])
+        .unwrap();
+        let configuration = args
+            .connection_configuration()
+            .expect("All connection flags are set");
+        assert_eq!("db.example.com", configuration.host);
+        assert_eq!("5433", configuration.port);
+        assert_eq!("ci", configuration.user);
+        assert_eq!("secret", configuration.password);
+        assert_eq!("experiments", configuration.database_name);
+
+        // With only some flags, the configuration file is still required
+        let args =
+            Args::try_parse_from(["phd-ex-cli", "--host", "db.example.com", "list-experiments"])
+                .unwrap();
+        assert!(args.connection_configuration().is_none());
+    }
+
+    #[test]
+    fn prompts_on_empty_input() {
+        // Empty input (e.g. stdin redirected from /dev/null) must yield an error instead of an empty answer
+        assert!(read_string_with_prompt("Enter something: ", std::io::empty()).is_err());
+        assert!(read_bool_with_prompt("Are you sure?", std::io::empty()).is_err());
+
+        {
+            let res = read_string_with_prompt("Enter something: ", "  value \n".as_bytes());
+            assert!(res.is_ok());
+            assert_eq!("value", res.unwrap());
+        }
+        {
+            let res = read_bool_with_prompt("Are you sure?", "y\n".as_bytes());
+            assert!(res.is_ok());
+            assert!(res.unwrap());
+        }
+        {
+            let res = read_bool_with_prompt("Are you sure?", "n\n".as_bytes());
+            assert!(res.is_ok());
+            assert!(!res.unwrap());
+        }
+    }
 }
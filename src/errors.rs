@@ -0,0 +1,43 @@
+use std::fmt::Display;
+
+/// Failure modes of this library that callers may want to handle. All functions still return `anyhow::Error`, which
+/// contains an `ExarError` in its chain of causes if the error is one of these failures. Use `ExarError::find_in` to
+/// get it. Failed connections to the database are reported as `ConnectionError` instead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExarError {
+    /// There is no experiment with the given name in the database
+    ExperimentNotFound(String),
+    /// An experiment with the same name but a different description, researcher or different variables exists in the
+    /// database. Contains a description of the difference
+    ExperimentMismatch(String),
+    /// A value was given for a variable that is not a variable of the experiment
+    UnknownVariable {
+        experiment: String,
+        variable: String,
+    },
+    /// A run has no values for the given required variables
+    MissingValues(Vec<String>),
+    /// A number was required, but the variable has a value that is not a number
+    NotANumber { variable: String, value: String },
+}
+
+impl ExarError {
+    /// Returns the `ExarError` in the chain of causes of `error`, if there is one
+    pub fn find_in(error: &anyhow::Error) -> Option<&ExarError> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+}
+
+impl Display for ExarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ExperimentNotFound(name) => write!(f, "No experiment with name \"{name}\" exists"),
+            Self::ExperimentMismatch(difference) => write!(f, "Experiment data does not match data of known experiment in the DB! {difference}. Either give the experiment a new unique name, or call `Experiment::override_existing` if you want to replace the experiment data with new data!"),
+            Self::UnknownVariable { experiment, variable } => write!(f, "{variable} is not a variable of experiment {experiment}"),
+            Self::MissingValues(variables) => write!(f, "The function passed to `run` must return a value for each required variable in this experiment! Missing values for variable(s) {}", variables.join(", ")),
+            Self::NotANumber { variable, value } => write!(f, "Variable {variable} has the non-numeric value \"{value}\""),
+        }
+    }
+}
+
+impl std::error::Error for ExarError {}
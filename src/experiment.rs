@@ -1,21 +1,40 @@
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::Read,
+    ops::{Deref, Range},
+    path::PathBuf,
     sync::Mutex,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
-use crate::{connect, gen_unique_id, Measurement, RawRun, Run, Variable, VariableTemplate};
+use crate::{
+    base_unit, commit, connect, duration_in_unit, ensure_schema_table, execute,
+    fallback::FallbackRun, gen_unique_id, gen_unused_id, gen_unused_ids, ignore_poison, now,
+    predicates::parse_numeric_value, runs::sort_measurements_by_variable_name, ArchivedRun,
+    ArchivedVariable, ConnectionError, ExarError, ExperimentArchive, Expression, Measurement,
+    MeasurementPredicate, RawRun, Run, RunStream, Variable, VariableTemplate,
+};
 
 use anyhow::{anyhow, bail, Context, Result};
-use log::info;
-use postgres::{Client, GenericClient};
+use log::{info, warn};
+use postgres::{types::ToSql, GenericClient, Transaction};
+use rayon::prelude::*;
+use serde::Deserialize;
 use tabled::builder::Builder;
 
+/// Maximum number of rows per multi-row INSERT statement. With 5 columns per row, this stays well below the limit of
+/// 65535 parameters per statement
+const MAX_ROWS_PER_INSERT: usize = 1000;
+
 /// Context for memorizing variable values while running an experiment. This object is thread-safe
 /// so that values can be added safely from multi-threaded code!
 pub struct RunContext<'a> {
     experiment: &'a Experiment,
     variable_values: Mutex<HashMap<&'a Variable, String>>,
+    /// Variables that were explicitly marked as having no value in this run (see `add_missing`)
+    missing_variables: Mutex<HashSet<&'a Variable>>,
+    label: Mutex<Option<String>>,
 }
 
 impl<'a> RunContext<'a> {
@@ -23,6 +42,8 @@ impl<'a> RunContext<'a> {
         Self {
             experiment,
             variable_values: Default::default(),
+            missing_variables: Default::default(),
+            label: Default::default(),
         }
     }
 
@@ -33,26 +54,285 @@ impl<'a> RunContext<'a> {
     ///
     /// If the current experiment has no variable with the given name
     pub fn add_value_by_name<S: AsRef<str>, V: ToString>(&self, variable_name: S, value: V) {
-        let variable = self
-            .experiment
+        let variable = self.variable_by_name(variable_name.as_ref());
+        self.insert_value(variable, value.to_string());
+    }
+
+    /// Adds a duration as the value for the variable with the given `variable_name`. The duration is converted into the
+    /// unit of the variable, so e.g. a duration of 1500 milliseconds is stored as `1.5` for a variable with unit "s"
+    ///
+    /// # panics
+    ///
+    /// If the current experiment has no variable with the given name, or if the unit of the variable is not a unit of
+    /// time (see `duration_in_unit`)
+    pub fn add_duration_by_name<S: AsRef<str>>(&self, variable_name: S, duration: Duration) {
+        let variable = self.variable_by_name(variable_name.as_ref());
+        let value = duration_in_unit(duration, variable.template().unit()).unwrap_or_else(|| {
+            panic!(
+                "Variable {} has unit \"{}\" which is not a unit of time!",
+                variable.template().name(),
+                variable.template().unit()
+            )
+        });
+        self.insert_value(variable, value.to_string());
+    }
+
+    /// Explicitly records that the variable with the given `variable_name` has no value in this run, e.g. because the
+    /// measurement failed. The run is then stored with a NULL measurement for this variable, even if the variable is not
+    /// optional. Adding a value for the variable afterwards replaces the missing value
+    ///
+    /// # panics
+    ///
+    /// If the current experiment has no variable with the given name
+    pub fn add_missing<S: AsRef<str>>(&self, variable_name: S) {
+        let variable = self.variable_by_name(variable_name.as_ref());
+
+        let mut values = ignore_poison(self.variable_values.lock());
+        let mut missing_variables = ignore_poison(self.missing_variables.lock());
+        values.remove(variable);
+        missing_variables.insert(variable);
+    }
+
+    /// Sets a human-readable label for the current run, which can be used instead of the run ID to refer to the run (see
+    /// `Experiment::run_from_label`). Labels must be unique among the runs of an experiment, storing the run fails
+    /// otherwise
+    pub fn set_label<S: Into<String>>(&self, label: S) {
+        let mut current_label = ignore_poison(self.label.lock());
+        *current_label = Some(label.into());
+    }
+
+    fn insert_value(&self, variable: &'a Variable, value: String) {
+        let mut values = ignore_poison(self.variable_values.lock());
+        let mut missing_variables = ignore_poison(self.missing_variables.lock());
+        missing_variables.remove(variable);
+        values.insert(variable, value);
+    }
+
+    /// Returns the measured values and the label of the run. Variables without a value are not part of the measured
+    /// values. Returns an error if a value is missing for a variable that is not optional and that was not explicitly
+    /// marked as missing
+    fn into_measured_values(self) -> Result<(HashMap<&'a Variable, String>, Option<String>)> {
+        let mut measured_variables = ignore_poison(self.variable_values.into_inner());
+        let mut missing_variables = ignore_poison(self.missing_variables.into_inner());
+        let label = ignore_poison(self.label.into_inner());
+
+        self.experiment
+            .add_derived_values(&mut measured_variables, &mut missing_variables)?;
+        self.experiment
+            .ensure_required_variables_are_measured(&measured_variables, &missing_variables)?;
+
+        Ok((measured_variables, label))
+    }
+
+    fn variable_by_name(&self, variable_name: &str) -> &'a Variable {
+        self.experiment
             .variables()
-            .find(|v| v.template().name() == variable_name.as_ref())
-            .expect("No variable with the given name found in the current experiment!");
+            .find(|v| v.template().name() == variable_name)
+            .expect("No variable with the given name found in the current experiment!")
+    }
+}
 
-        let mut values = self.variable_values.lock().expect("Lock was poisoned");
-        values.insert(variable, value.to_string());
+/// A run of an experiment that was started through `Experiment::begin_run` and is stored once `finish` is called.
+/// Dereferences to a `RunContext`, so values are added through `RunContext::add_value_by_name` etc.
+pub struct RunBuilder<'a> {
+    context: RunContext<'a>,
+}
+
+impl<'a> RunBuilder<'a> {
+    /// Stores this run in the database and returns its ID. Returns an error if a value is missing for a variable
+    /// that is not optional. Just as `Experiment::run`, this writes the run to the fallback file of the experiment (if
+    /// set) if the database is unreachable
+    pub fn finish(self) -> Result<String> {
+        let experiment = self.context.experiment;
+        let (measured_variables, label) = self.context.into_measured_values()?;
+        experiment.store_measured_run(measured_variables, label)
+    }
+}
+
+impl<'a> Deref for RunBuilder<'a> {
+    type Target = RunContext<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.context
+    }
+}
+
+/// A batch of experiment runs that are stored within a single database transaction. Created through
+/// `Experiment::with_transaction`
+pub struct RunBatch<'a> {
+    experiment: &'a Experiment,
+    transaction: Transaction<'a>,
+    runs: Vec<(String, i32, HashMap<&'a Variable, String>)>,
+}
+
+impl RunBatch<'_> {
+    /// Runs the experiment as part of this batch. This works just like `Experiment::run`, but the run is only stored
+    /// in the database once the whole batch is committed. Returns the ID of the new run
+    pub fn run<F: FnOnce(&RunContext) -> Result<()>>(&mut self, func: F) -> Result<String> {
+        let (measured_variables, label) = self.experiment.execute_run_function(func)?;
+        let run_id = gen_unused_id("experiment_runs", &mut self.transaction)?;
+        let run_number = self
+            .experiment
+            .insert_run_with_measurements(
+                &measured_variables,
+                &run_id,
+                label.as_deref(),
+                now(),
+                &mut self.transaction,
+            )
+            .context("Failed to insert new experiment run into the database")?;
+        self.runs
+            .push((run_id.clone(), run_number, measured_variables));
+        Ok(run_id)
     }
 }
 
 /// Experiment definition after insertion into the DB or fetching from the DB
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Debug)]
 pub struct Experiment {
     id: String,
     name: String,
     description: String,
     researcher: String,
     required_variables: HashSet<Variable>,
+    optional_variables: HashSet<String>,
+    /// Expressions of the variables whose values are computed from other variables, by variable name
+    derived_variables: HashMap<String, Expression>,
     autolog_runs: bool,
+    fallback_file: Option<PathBuf>,
+}
+
+/// Builder for an `Experiment`, created through `Experiment::builder`
+#[derive(Debug, Default, Clone)]
+pub struct ExperimentBuilder {
+    name: Option<String>,
+    description: String,
+    researcher: String,
+    variables: Vec<(VariableTemplate, bool)>,
+    /// Names and expressions of derived variables, which are also part of `variables`
+    derived_variables: Vec<(String, String)>,
+}
+
+impl ExperimentBuilder {
+    /// Sets the name of the experiment. This is required
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the description of the experiment
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the researcher(s) of the experiment
+    pub fn researcher(mut self, researcher: impl Into<String>) -> Self {
+        self.researcher = researcher.into();
+        self
+    }
+
+    /// Adds a variable that has to be measured in every run of the experiment
+    pub fn variable(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        description: impl Into<Cow<'static, str>>,
+        unit: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.variables.push((
+            VariableTemplate::new(name.into(), description.into(), unit.into()),
+            false,
+        ));
+        self
+    }
+
+    /// Adds a variable that runs of the experiment may leave unmeasured (see `Experiment::set_variable_optional`)
+    pub fn optional_variable(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        description: impl Into<Cow<'static, str>>,
+        unit: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.variables.push((
+            VariableTemplate::new(name.into(), description.into(), unit.into()),
+            true,
+        ));
+        self
+    }
+
+    /// Adds a variable whose value is computed from the values of other variables of the experiment through the given
+    /// arithmetic `expression`, e.g. `"Bytes / Runtime"` (see `Experiment::set_variable_derived`)
+    pub fn derived_variable(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        description: impl Into<Cow<'static, str>>,
+        unit: impl Into<Cow<'static, str>>,
+        expression: impl Into<String>,
+    ) -> Self {
+        let variable = VariableTemplate::new(name.into(), description.into(), unit.into());
+        self.derived_variables
+            .push((variable.name().to_owned(), expression.into()));
+        self.variables.push((variable, false));
+        self
+    }
+
+    /// Builds the experiment through `Experiment::new`, so the experiment is inserted into the database if it does not
+    /// exist yet. Returns an error if no name was set, if multiple variables have the same name or if the expression of
+    /// a derived variable is invalid
+    pub fn build(self) -> Result<Experiment> {
+        let name = self
+            .name
+            .ok_or_else(|| anyhow!("An experiment requires a name"))?;
+
+        let mut variable_names = HashSet::new();
+        for (variable, _) in &self.variables {
+            if !variable_names.insert(variable.name()) {
+                bail!(
+                    "Variable names must be unique within an experiment, but found multiple variables named {}",
+                    variable.name()
+                );
+            }
+        }
+
+        let mut experiment = Experiment::new(
+            name,
+            self.description,
+            self.researcher,
+            self.variables
+                .iter()
+                .map(|(variable, _)| variable.clone())
+                .collect(),
+        )?;
+        for (variable, optional) in &self.variables {
+            experiment.set_variable_optional(variable.name(), *optional)?;
+        }
+        for (variable_name, expression) in &self.derived_variables {
+            experiment.set_variable_derived(variable_name, expression)?;
+        }
+        Ok(experiment)
+    }
+}
+
+/// Definition of an experiment as parsed by `Experiment::from_json`
+#[derive(Deserialize)]
+struct ExperimentDefinition {
+    name: String,
+    #[serde(default)]
+    description: String,
+    researcher: String,
+    variables: Vec<VariableDefinition>,
+}
+
+#[derive(Deserialize)]
+struct VariableDefinition {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    unit: String,
+    #[serde(default)]
+    optional: bool,
+    expression: Option<String>,
 }
 
 impl Experiment {
@@ -66,18 +346,36 @@ impl Experiment {
         researcher: String,
         required_variables: HashSet<VariableTemplate>,
     ) -> Result<Self> {
+        Self::ensure_unique_variable_names(&required_variables)?;
+
         let mut db_client =
             crate::postgres::connect().context("Could not connect to postgres DB")?;
-        if let Some(experiment) = Self::get_experiment_from_db_by_name(&name, &mut db_client)
+        let mut transaction = db_client
+            .transaction()
+            .context("Can't start database transaction")?;
+        // Without a lock, concurrent calls with the same name could all find that the experiment does not exist yet and
+        // try to insert it. The lock is released at the end of the transaction, after which the experiment exists
+        transaction
+            .execute("SELECT pg_advisory_xact_lock(hashtext($1))", &[&name])
+            .context("Failed to lock experiment name")?;
+        if let Some(experiment) = Self::get_experiment_from_db_by_name(&name, &mut transaction)
             .context("Failed to check database for the existence of this experiment")?
         {
             // Known experiment. check that description and researcher still match, otherwise raise an error!
             if experiment.description != description {
-                bail!("Experiment data does not match data of known experiment in the DB! Expected description string {} but got description string {}. Either give the experiment a new unique name, or call `Experiment::override_existing` if you want to replace the experiment data with new data!", description, experiment.description);
+                return Err(ExarError::ExperimentMismatch(format!(
+                    "Expected description string {} but got description string {}",
+                    description, experiment.description
+                ))
+                .into());
             }
 
             if experiment.researcher != researcher {
-                bail!("Experiment data does not match data of known experiment in the DB! Expected researcher(s) {} but got researcher(s) {}. Either give the experiment a new unique name, or call `Experiment::override_existing` if you want to replace the experiment data with new data!", researcher, experiment.researcher);
+                return Err(ExarError::ExperimentMismatch(format!(
+                    "Expected researcher(s) {} but got researcher(s) {}",
+                    researcher, experiment.researcher
+                ))
+                .into());
             }
 
             let diff_variables = experiment
@@ -92,31 +390,20 @@ impl Experiment {
             if experiment.required_variables.len() != required_variables.len()
                 || !diff_variables.is_empty()
             {
-                bail!("Experiment data does not match data of known experiment in the DB! Found unexpected variables {:#?}. If this is a new experiment, either give it a unique name, or call `Experiment::override_existing` if you want to replace the experiment data with new data!", diff_variables);
+                return Err(ExarError::ExperimentMismatch(format!(
+                    "Found unexpected variables {diff_variables:#?}"
+                ))
+                .into());
             }
 
             Ok(experiment)
         } else {
             // New experiment!
-            let mut transaction = db_client
-                .transaction()
-                .context("Can't start database transaction")?;
-            let variables = required_variables
-                .into_iter()
-                .map(|variable| -> Result<Variable> {
-                    // Check if we have a matching variable in the DB!
-                    let matching_variable = variable
-                        .fetch_from_db(&mut transaction)
-                        .context("Failed to check database for existing variable")?;
-                    if let Some(variable) = matching_variable {
-                        Ok(variable)
-                    } else {
-                        variable.insert_into_db(&mut transaction)
-                    }
-                })
-                .collect::<Result<HashSet<_>, _>>()
+            let variables = Self::fetch_or_insert_variables(required_variables, &mut transaction)
                 .context("Inserting variables into database failed")?;
-            let experiment_id = Self::insert_new_experiment_into_db(
+            let experiment_id = gen_unused_id("experiments", &mut transaction)?;
+            Self::insert_new_experiment_into_db(
+                &experiment_id,
                 &name,
                 &description,
                 &researcher,
@@ -124,8 +411,7 @@ impl Experiment {
                 &mut transaction,
             )
             .context("Failed to insert new experiment into database")?;
-            transaction
-                .commit()
+            commit(transaction)
                 .context("Failed to commit transaction for new experiment and variables")?;
 
             Ok(Self {
@@ -134,27 +420,158 @@ impl Experiment {
                 name,
                 required_variables: variables,
                 researcher,
+                optional_variables: Default::default(),
+                derived_variables: Default::default(),
                 autolog_runs: false,
+                fallback_file: None,
             })
         }
     }
 
-    /// Overrides an existing experiment in the database with new values
+    /// Creates an experiment from a JSON definition, which is then passed to `Experiment::new`. The definition has the
+    /// following form, where `description`, `unit` and `optional` of a variable can be omitted. Variables with an
+    /// `expression` are derived from other variables (see `Experiment::set_variable_derived`):
+    ///
+    /// ```json
+    /// {
+    ///     "name": "My experiment",
+    ///     "description": "What this experiment does",
+    ///     "researcher": "Jane Doe",
+    ///     "variables": [
+    ///         { "name": "Dataset", "description": "The dataset that was used" },
+    ///         { "name": "Runtime", "unit": "ms" },
+    ///         { "name": "Notes", "optional": true },
+    ///         { "name": "Runs per second", "expression": "1000 / Runtime" }
+    ///     ]
+    /// }
+    /// ```
+    pub fn from_json<R: Read>(reader: R) -> Result<Self> {
+        let definition: ExperimentDefinition =
+            serde_json::from_reader(reader).context("Failed to parse experiment definition")?;
+        let mut builder = Self::builder()
+            .name(definition.name)
+            .description(definition.description)
+            .researcher(definition.researcher);
+        for variable in definition.variables {
+            builder = if let Some(expression) = variable.expression {
+                builder.derived_variable(
+                    variable.name,
+                    variable.description,
+                    variable.unit,
+                    expression,
+                )
+            } else if variable.optional {
+                builder.optional_variable(variable.name, variable.description, variable.unit)
+            } else {
+                builder.variable(variable.name, variable.description, variable.unit)
+            };
+        }
+        builder.build()
+    }
+
+    /// Returns a builder for an experiment. This is an alternative to `Experiment::new` that does not require
+    /// collecting the variable templates into a `HashSet` and that can mark variables as optional
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use experiment_archiver::{Experiment, UNIT_MILLISECONDS};
+    ///
+    /// let experiment = Experiment::builder()
+    ///     .name("Performance Test 1")
+    ///     .description("This experiment tests general performance of tool XYZ")
+    ///     .researcher("Name of the researchers")
+    ///     .variable("Dataset", "The dataset used for this experiment", "none")
+    ///     .variable("Runtime", "The runtime of the experiment", UNIT_MILLISECONDS)
+    ///     .optional_variable("Notes", "Remarks about the run", "")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> ExperimentBuilder {
+        ExperimentBuilder::default()
+    }
+
+    /// Overrides the description, researcher and variables of the existing experiment with the given `name` and returns
+    /// the updated experiment. The experiment keeps its ID, tags and runs. Measurements of runs are stored per
+    /// variable, so existing runs keep their values for all variables that are still part of the experiment, and have
+    /// no values for newly added variables. Fails if no experiment with `name` exists
     pub fn override_existing(
-        _name: String,
-        _description: String,
-        _researcher: String,
-        _required_variables: HashSet<VariableTemplate>,
-    ) -> Result<()> {
-        // TODO Instead of overriding, we could add support for versioning of experiments
-        todo!()
+        name: String,
+        description: String,
+        researcher: String,
+        required_variables: HashSet<VariableTemplate>,
+    ) -> Result<Self> {
+        Self::ensure_unique_variable_names(&required_variables)?;
+
+        let mut db_client =
+            crate::postgres::connect().context("Could not connect to postgres DB")?;
+        let mut transaction = db_client
+            .transaction()
+            .context("Can't start database transaction")?;
+
+        let experiment_id: String = transaction
+            .query_opt(
+                "SELECT id FROM experiments WHERE name = $1 FOR UPDATE;",
+                &[&name],
+            )
+            .context("Failed to query experiments by name")?
+            .ok_or_else(|| ExarError::ExperimentNotFound(name.clone()))?
+            .get(0);
+
+        execute(
+            &mut transaction,
+            "UPDATE experiments SET description = $1, researcher = $2 WHERE id = $3;",
+            &[&description, &researcher, &experiment_id],
+        )
+        .context("Failed to update experiment")?;
+
+        let variables = Self::fetch_or_insert_variables(required_variables, &mut transaction)
+            .context("Inserting variables into database failed")?;
+        execute(
+            &mut transaction,
+            "DELETE FROM experiment_variables WHERE experiment_id = $1;",
+            &[&experiment_id],
+        )
+        .context("Failed to delete experiment/variable relations")?;
+        for variable in &variables {
+            Self::insert_experiment_variable_relation(
+                &experiment_id,
+                variable.id(),
+                &mut transaction,
+            )
+            .context("Failed to insert experiment/variable relation")?;
+        }
+
+        commit(transaction).context("Failed to commit transaction for overridden experiment")?;
+
+        Ok(Self {
+            description,
+            id: experiment_id,
+            name,
+            required_variables: variables,
+            researcher,
+            optional_variables: Default::default(),
+            derived_variables: Default::default(),
+            autolog_runs: false,
+            fallback_file: None,
+        })
     }
 
     /// Tries to fetch the experiment with the given name from the database
     pub fn from_name(name: &str) -> Result<Option<Self>> {
         let mut db_client =
             crate::postgres::connect().context("Could not connect to postgres DB")?;
-        Self::get_experiment_from_db_by_name(name, &mut db_client)
+        Self::get_experiment_from_db_by_name(name, &mut *db_client)
+    }
+
+    /// Returns whether an experiment with the given name exists in the database, without fetching it
+    pub fn exists(name: &str) -> Result<bool> {
+        let mut db_client =
+            crate::postgres::connect().context("Could not connect to postgres DB")?;
+        let row = db_client
+            .query_opt("SELECT 1 FROM experiments WHERE name = $1", &[&name])
+            .context("Failed to execute query")?;
+        Ok(row.is_some())
     }
 
     /// Tries to fetch the experiment for the run with the given ID
@@ -162,10 +579,10 @@ impl Experiment {
         let mut db_client =
             crate::postgres::connect().context("Could not connect to postgres DB")?;
         let raw_run =
-            RawRun::from_id(run_id, &mut db_client).context("Failed to fetch run from DB")?;
+            RawRun::from_id(run_id, &mut *db_client).context("Failed to fetch run from DB")?;
         match raw_run {
             None => Ok(None),
-            Some(raw_run) => Self::get_experiment_from_db_by_raw_run(&raw_run, &mut db_client),
+            Some(raw_run) => Self::get_experiment_from_db_by_raw_run(&raw_run, &mut *db_client),
         }
     }
 
@@ -173,53 +590,260 @@ impl Experiment {
     /// The function itself has to return a set of all variables for this experiment run together with the values for
     /// those variables. Since all measurements are stored in the same DB table, Variable values are stored as strings.
     /// The ID for the experiment run is returned, through this ID information about the run can be queried from the DB
+    ///
+    /// If a fallback file is set (see `set_fallback_file`) and no connection to the database can be established, the run
+    /// is appended to the fallback file instead and its ID is returned as usual
     pub fn run<F: FnOnce(&RunContext) -> Result<()>>(&self, func: F) -> Result<String> {
-        let context = RunContext::from_experiment(&self);
-        func(&context).context("Experiment function failed")?;
+        let run = self.begin_run();
+        func(&run).context("Experiment function failed")?;
+        run.finish()
+    }
 
-        let measured_variables = context
-            .variable_values
-            .into_inner()
-            .expect("Mutex was poisoned");
+    /// Starts a new run of this experiment without an experiment function. Values are added to the returned
+    /// `RunBuilder` just like to a `RunContext`, e.g. in multiple stages with other work in between. The run is stored
+    /// when calling `RunBuilder::finish`, which works just like the end of `Experiment::run`. Dropping the `RunBuilder`
+    /// without calling `finish` discards the run
+    pub fn begin_run(&self) -> RunBuilder<'_> {
+        RunBuilder {
+            context: RunContext::from_experiment(self),
+        }
+    }
 
-        if self.required_variables.len() != measured_variables.len()
-            || self
-                .required_variables
-                .iter()
-                .any(|variable| !measured_variables.contains_key(variable))
-        {
-            bail!("The function passed to `run` must return a value for each required variable in this experiment!");
+    /// Stores a run with the given measured values, or writes it to the fallback file if the database is unreachable.
+    /// Returns the ID of the run
+    fn store_measured_run(
+        &self,
+        measured_variables: HashMap<&Variable, String>,
+        label: Option<String>,
+    ) -> Result<String> {
+        let timestamp = now();
+
+        let (run_id, run_number) = match self.store_run(
+            &measured_variables,
+            label.as_deref(),
+            timestamp,
+        ) {
+            Ok(stored_run) => stored_run,
+            Err(why) => match &self.fallback_file {
+                Some(fallback_file) if why.chain().any(|cause| cause.is::<ConnectionError>()) => {
+                    // Without a connection, the ID can't be checked against the IDs of existing runs
+                    let run_id = gen_unique_id();
+                    warn!(
+                        "Could not connect to the database, writing run {run_id} to fallback file {}",
+                        fallback_file.display()
+                    );
+                    FallbackRun::new(
+                        self,
+                        &run_id,
+                        label.as_deref(),
+                        timestamp,
+                        &measured_variables,
+                    )
+                    .append_to_file(fallback_file)
+                    .context("Failed to write run to fallback file")?;
+                    return Ok(run_id);
+                }
+                _ => return Err(why),
+            },
+        };
+
+        if self.autolog_runs {
+            Self::log_run(&measured_variables, run_number);
+        }
+
+        Ok(run_id)
+    }
+
+    /// Stores multiple runs of this experiment at once, without executing an experiment function. Each run is given as
+    /// a collection of (variable name, value) pairs. All runs are inserted within a single transaction using multi-row
+    /// INSERT statements, which is much faster than calling `run` for each run if there are many runs. Returns the IDs
+    /// of the new runs, in the same order as `runs`. If any run has a value for an unknown variable or is missing a
+    /// value for a required variable, none of the runs are stored
+    pub fn run_many<I, R, K, V>(&self, runs: I) -> Result<Vec<String>>
+    where
+        I: IntoIterator<Item = R>,
+        R: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: ToString,
+    {
+        let runs = runs
+            .into_iter()
+            .enumerate()
+            .map(
+                |(index, values)| -> Result<(HashMap<&Variable, String>, Option<String>)> {
+                    let mut measured_variables = values
+                        .into_iter()
+                        .map(|(name, value)| {
+                            let variable = self
+                                .required_variables
+                                .iter()
+                                .find(|variable| variable.template().name() == name.as_ref())
+                                .ok_or_else(|| ExarError::UnknownVariable {
+                                    experiment: self.name.clone(),
+                                    variable: name.as_ref().to_owned(),
+                                })
+                                .with_context(|| {
+                                    format!("Run {index} has a value for an unknown variable")
+                                })?;
+                            Ok((variable, value.to_string()))
+                        })
+                        .collect::<Result<HashMap<_, _>>>()?;
+                    let mut missing_variables = HashSet::new();
+                    self.add_derived_values(&mut measured_variables, &mut missing_variables)
+                        .with_context(|| {
+                            format!("Failed to compute derived values of run {index}")
+                        })?;
+                    self.ensure_required_variables_are_measured(
+                        &measured_variables,
+                        &missing_variables,
+                    )
+                    .with_context(|| format!("Run {index} is incomplete"))?;
+                    Ok((measured_variables, None))
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+        self.insert_many_runs(&runs)
+    }
+
+    /// Executes `run_fn` `count` times in parallel on the rayon thread pool and stores all runs afterwards, just as
+    /// `run_many` does. The returned run IDs are ordered by the index of the execution, not by the order in which the
+    /// executions finished. Since the executions run concurrently, `run_fn` must be safe to call from multiple threads
+    /// at once, e.g. it must not write to shared files without synchronization. If any execution fails, none of the
+    /// runs are stored
+    pub fn run_parallel<F>(&self, count: usize, run_fn: F) -> Result<Vec<String>>
+    where
+        F: Fn(&RunContext) -> Result<()> + Sync,
+    {
+        let runs = (0..count)
+            .into_par_iter()
+            .map(|index| {
+                self.execute_run_function(&run_fn)
+                    .with_context(|| format!("Run {index} failed"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.insert_many_runs(&runs)
+    }
+
+    /// Inserts the given runs (measured values and label of each run) within a single transaction using multi-row
+    /// INSERT statements. Returns the IDs of the new runs, in the same order as `runs`
+    fn insert_many_runs(
+        &self,
+        runs: &[(HashMap<&Variable, String>, Option<String>)],
+    ) -> Result<Vec<String>> {
+        if runs.is_empty() {
+            return Ok(vec![]);
         }
 
         let mut db_client =
             crate::postgres::connect().context("Could not connect to postgres DB")?;
-        let last_run_number = self
-            .get_current_run_number_from_db(&mut db_client)
+        let mut transaction = db_client.transaction().context("Can't start transaction")?;
+
+        let first_run_number = self
+            .get_current_run_number_from_db(&mut transaction)
             .context("Can't get run number of previous run of this experiment")?
-            .unwrap_or(0);
+            .unwrap_or(0)
+            + 1;
+        let run_numbers = (0..runs.len() as i32)
+            .map(|index| first_run_number + index)
+            .collect::<Vec<_>>();
+        let run_ids = gen_unused_ids("experiment_runs", runs.len(), &mut transaction)?;
+        let timestamp = now();
+
+        let run_params = run_numbers
+            .iter()
+            .zip(run_ids.iter())
+            .zip(runs.iter())
+            .flat_map(
+                |((run_number, run_id), (_, label))| -> [&(dyn ToSql + Sync); 5] {
+                    [run_number, &self.id, run_id, &timestamp, label]
+                },
+            )
+            .collect::<Vec<_>>();
+        Self::insert_rows("experiment_runs", 5, &run_params, &mut transaction)
+            .context("Failed to insert new experiment runs into the database")?;
+
+        // Just as for single runs, optional variables without a value are stored as NULL measurements. There is one
+        // measurement per run and variable, so measurement `index` belongs to run `index / variables.len()`
+        let variables = self.required_variables.iter().collect::<Vec<_>>();
+        let variable_ids = variables
+            .iter()
+            .map(|variable| variable.id())
+            .collect::<Vec<_>>();
+        let values = runs
+            .iter()
+            .flat_map(|(measured_variables, _)| {
+                variables
+                    .iter()
+                    .map(move |variable| measured_variables.get(*variable).cloned())
+            })
+            .collect::<Vec<_>>();
+        let numeric_values = values
+            .iter()
+            .map(|value| value.as_deref().and_then(parse_numeric_value))
+            .collect::<Vec<_>>();
+        let measurement_params = (0..values.len())
+            .flat_map(|index| -> [&(dyn ToSql + Sync); 6] {
+                [
+                    &self.id,
+                    &variable_ids[index % variables.len()],
+                    &run_ids[index / variables.len()],
+                    &values[index],
+                    &timestamp,
+                    &numeric_values[index],
+                ]
+            })
+            .collect::<Vec<_>>();
+        Self::insert_rows("measurements", 6, &measurement_params, &mut transaction)
+            .context("Failed to insert new measurements into the database")?;
 
-        // Insert a new run and one measurement for each variable
-        let mut transaction = db_client.transaction().context("Can't start transaction")?;
-        let run_id = self
-            .insert_run(last_run_number + 1, &mut transaction)
-            .context("Failed to insert new experiment run into the database")?;
-        for (variable, value) in &measured_variables {
-            self.insert_measurement(&variable, &run_id, value.clone(), &mut transaction)
-                .context("Failed to insert new measurement")?;
+        commit(transaction)
+            .context("Failed to commit transaction for inserting experiment runs")?;
+
+        if self.autolog_runs {
+            for ((measured_variables, _), run_number) in runs.iter().zip(run_numbers.iter()) {
+                Self::log_run(measured_variables, *run_number);
+            }
         }
-        transaction
-            .commit()
-            .context("Failed to commit transaction for inserting result of experiment run")?;
+
+        Ok(run_ids)
+    }
+
+    /// Executes multiple runs of this experiment within a single database transaction. `func` receives a `RunBatch`
+    /// and can call `RunBatch::run` as often as needed. If `func` returns `Ok`, the transaction is committed and the
+    /// IDs of all runs are returned. If `func` (or any of the runs) fails, the transaction is rolled back and none of
+    /// the runs are stored in the database
+    pub fn with_transaction<F: FnOnce(&mut RunBatch) -> Result<()>>(
+        &self,
+        func: F,
+    ) -> Result<Vec<String>> {
+        let mut db_client =
+            crate::postgres::connect().context("Could not connect to postgres DB")?;
+        let transaction = db_client.transaction().context("Can't start transaction")?;
+        let mut batch = RunBatch {
+            experiment: self,
+            transaction,
+            runs: vec![],
+        };
+        func(&mut batch).context("Batch function failed, rolling back all runs of the batch")?;
+
+        let RunBatch {
+            transaction, runs, ..
+        } = batch;
+        commit(transaction)
+            .context("Failed to commit transaction for inserting experiment runs")?;
 
         if self.autolog_runs {
-            Self::log_run(&measured_variables, last_run_number + 1);
+            for (_, run_number, measured_variables) in &runs {
+                Self::log_run(measured_variables, *run_number);
+            }
         }
 
-        Ok(run_id)
+        Ok(runs.into_iter().map(|(run_id, _, _)| run_id).collect())
     }
 
-    /// Fetch all measurements for the given run of this experiment from the DB
-    pub fn measurements_for_run(&self, run_id: &str) -> Result<Vec<Measurement>> {
+    /// Fetch all measurements for the given run of this experiment from the DB. The measurements are sorted by
+    /// variable name
+    pub fn measurements_for_run(&self, run_id: &str) -> Result<Vec<Measurement<'_>>> {
         let mut client = connect().context("Failed to connect to DB")?;
 
         let run_number_row = client
@@ -236,12 +860,25 @@ impl Experiment {
         }
         let run_number: i32 = run_number_row[0].get(0);
 
-        // There must be one measurement for each of the variables of this experiment!
-        self.variables()
-            .map(|variable| {
-                Measurement::fetch_by_run_and_variable(run_id, run_number, variable, &mut client)
-            })
-            .collect()
+        // There should be one measurement for each of the variables of this experiment, but the variables might have
+        // changed since this run was recorded. Missing measurements are skipped
+        let mut measurements = Vec::with_capacity(self.required_variables.len());
+        for variable in self.variables() {
+            match Measurement::fetch_by_run_and_variable(
+                run_id,
+                run_number,
+                variable,
+                &mut *client,
+            )? {
+                Some(measurement) => measurements.push(measurement),
+                None => warn!(
+                    "Run {run_id} has no measurement for variable {}",
+                    variable.template().name()
+                ),
+            }
+        }
+        sort_measurements_by_variable_name(&mut measurements);
+        Ok(measurements)
     }
 
     /// Fetch the data for all runs of this experiments
@@ -250,55 +887,147 @@ impl Experiment {
 
         let all_measurements_for_runs = client
             .query(
-                "SELECT experiment_runs.id, value, measurements.timestamp, runnumber, variableid FROM measurements INNER JOIN experiment_runs ON measurements.runid = experiment_runs.id WHERE experiment_runs.experimentid = $1",
+                "SELECT experiment_runs.id, value, measurements.timestamp, experiment_runs.timestamp AS run_timestamp, runnumber, label, variableid FROM measurements INNER JOIN experiment_runs ON measurements.runid = experiment_runs.id WHERE experiment_runs.experimentid = $1",
                 &[&self.id],
             )
             .context("Failed to execute query")?;
 
-        let mut measurements_per_run: HashMap<String, Vec<Measurement<'_>>> = Default::default();
+        // Label, timestamp and measurements for each run ID
+        let mut measurements_per_run: HashMap<
+            String,
+            (Option<String>, SystemTime, Vec<Measurement<'_>>),
+        > = Default::default();
 
         for row in all_measurements_for_runs {
             let run_id: &str = row.get("id");
             let variable_id: &str = row.get("variableid");
-            let value: String = row.get("value");
+            let value: Option<String> = row.get("value");
             let timestamp: SystemTime = row.get("timestamp");
             let run_number: i32 = row.get("runnumber");
+            let label: Option<String> = row.get("label");
+            let run_timestamp: SystemTime = row.get("run_timestamp");
 
-            let variable = self
+            // The experiment might have different variables than when this run was recorded. Measurements of variables
+            // that are not part of the experiment anymore are skipped
+            let Some(variable) = self
                 .required_variables
                 .iter()
                 .find(|variable| variable.id() == variable_id)
-                .ok_or(anyhow!("No matching variable found"))?;
+            else {
+                warn!("Skipping measurement of run {run_id} for variable {variable_id} which is not a variable of experiment {}", self.name);
+                continue;
+            };
 
             let measurement = Measurement::new(variable, value, timestamp, run_number);
 
-            if let Some(measurements) = measurements_per_run.get_mut(run_id) {
+            if let Some((_, _, measurements)) = measurements_per_run.get_mut(run_id) {
                 measurements.push(measurement);
             } else {
-                measurements_per_run.insert(run_id.to_string(), vec![measurement]);
+                measurements_per_run.insert(
+                    run_id.to_string(),
+                    (label, run_timestamp, vec![measurement]),
+                );
             }
         }
 
         let mut runs: Vec<Run<'_>> = measurements_per_run
             .into_iter()
-            .map(|(run_id, measurements)| {
-                Run::new(run_id, measurements[0].run_number() as usize, measurements)
+            .map(|(run_id, (label, timestamp, measurements))| {
+                Run::new(
+                    run_id,
+                    measurements[0].run_number() as usize,
+                    label,
+                    timestamp,
+                    measurements,
+                )
             })
             .collect();
-        runs.sort_by(|a, b| a.run_number().cmp(&b.run_number()));
+        runs.sort_by_key(|a| a.run_number());
 
         Ok(runs)
     }
 
+    /// Like `all_runs`, but returns an iterator that fetches the runs from the database in chunks while iterating,
+    /// instead of loading all runs into memory at once. Use this for experiments with many runs
+    pub fn stream_runs(&self) -> Result<RunStream<'_>> {
+        RunStream::new(self)
+    }
+
+    /// Returns the number of runs of this experiment. This is much cheaper than fetching the runs if only their number
+    /// is of interest
+    pub fn count_runs(&self) -> Result<usize> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        let row = client
+            .query_one(
+                "SELECT COUNT(*) FROM experiment_runs WHERE experimentid = $1",
+                &[&self.id],
+            )
+            .context("Failed to execute query")?;
+        let count: i64 = row.get(0);
+        Ok(count as usize)
+    }
+
+    /// Fetches the ID, run number and timestamp of all runs of this experiment, sorted by run number. This does not
+    /// load any measurements, so it is much cheaper than `all_runs` if only the runs themselves are of interest
+    pub fn run_headers(&self) -> Result<Vec<RawRun>> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        RawRun::all_of_experiment(self, &mut *client)
+    }
+
+    /// Like `run_headers`, but only fetches the runs that satisfy all of the `predicates`, e.g. all runs where the value of
+    /// variable "Runtime" is greater than 500
+    pub fn run_headers_matching(&self, predicates: &[MeasurementPredicate]) -> Result<Vec<RawRun>> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        RawRun::matching_of_experiment(self, predicates, &mut *client)
+    }
+
+    /// Fetches all runs of this experiment that satisfy all of the `predicates`, sorted by run number
+    pub fn runs_matching(&self, predicates: &[MeasurementPredicate]) -> Result<Vec<Run<'_>>> {
+        self.run_headers_matching(predicates)?
+            .iter()
+            .map(|raw_run| Run::from_raw_run(raw_run, self))
+            .collect()
+    }
+
+    /// Like `run_headers`, but only fetches a page of the runs. Skips the first `offset` runs and returns at most `limit`
+    /// runs, or all remaining runs if `limit` is `None`
+    pub fn run_headers_page(&self, limit: Option<usize>, offset: usize) -> Result<Vec<RawRun>> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        RawRun::page_of_experiment(self, limit, offset, &mut *client)
+    }
+
+    /// Fetches the run of this experiment with the given label (see `RunContext::set_label`) from the DB
+    pub fn run_from_label(&self, label: &str) -> Result<Option<Run<'_>>> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        let raw_run = RawRun::from_label_and_experiment(label, self, &mut *client)
+            .context("Failed to fetch run from DB")?;
+        match raw_run {
+            None => Ok(None),
+            Some(raw_run) => Run::from_raw_run(&raw_run, self).map(Some),
+        }
+    }
+
+    /// Fetches the most recent run of this experiment from the DB, i.e. the run with the latest timestamp, without fetching
+    /// any of the other runs. Returns `None` if this experiment has no runs
+    pub fn latest_run(&self) -> Result<Option<Run<'_>>> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        let raw_run = RawRun::latest_of_experiment(self, &mut *client)
+            .context("Failed to fetch run from DB")?;
+        match raw_run {
+            None => Ok(None),
+            Some(raw_run) => Run::from_raw_run(&raw_run, self).map(Some),
+        }
+    }
+
     pub fn run_from_id(&self, run_id: &str) -> Result<Option<Run<'_>>> {
         let mut client = connect().context("Failed to connect to DB")?;
         let raw_run =
-            RawRun::from_id(run_id, &mut client).context("Failed to fetch run from DB")?;
+            RawRun::from_id(run_id, &mut *client).context("Failed to fetch run from DB")?;
         match raw_run {
             None => Ok(None),
             Some(raw_run) => {
                 let run =
-                    Run::from_raw_run(&raw_run, &self).context("Failed to fetch run from DB")?;
+                    Run::from_raw_run(&raw_run, self).context("Failed to fetch run from DB")?;
                 Ok(Some(run))
             }
         }
@@ -329,12 +1058,260 @@ impl Experiment {
         self.required_variables.iter()
     }
 
+    /// Marks the variable with the given name as optional (or required, if `optional` is `false`). Runs of this
+    /// experiment may omit values for optional variables, in which case a NULL measurement is stored for the variable.
+    /// Just like autologging, this is a property of this `Experiment` object and is not stored in the database. By
+    /// default, all variables are required
+    pub fn set_variable_optional(&mut self, variable_name: &str, optional: bool) -> Result<()> {
+        if !self
+            .variables()
+            .any(|variable| variable.template().name() == variable_name)
+        {
+            bail!(
+                "No variable with name {variable_name} found in experiment {}",
+                self.name
+            );
+        }
+        if optional {
+            self.optional_variables.insert(variable_name.to_owned());
+        } else {
+            self.optional_variables.remove(variable_name);
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the variable with the given name is optional for this experiment
+    pub fn is_variable_optional(&self, variable_name: &str) -> bool {
+        self.optional_variables.contains(variable_name)
+    }
+
+    /// Marks the variable with the given name as derived, so that its value is computed from the values of other
+    /// variables through the arithmetic `expression` (see `Expression`), e.g. `"Bytes / Runtime"`. The value is computed
+    /// after the experiment function returns and is stored like any other measurement, so it must not be added to the
+    /// `RunContext`. If any referenced variable has no value in a run, the derived variable has no value either. Just
+    /// like optional variables, this is a property of this `Experiment` object and is not stored in the database.
+    /// Returns an error if the expression is invalid, references unknown variables or if derived variables would
+    /// reference each other cyclically
+    pub fn set_variable_derived(&mut self, variable_name: &str, expression: &str) -> Result<()> {
+        let expression = expression.parse::<Expression>().with_context(|| {
+            format!("Invalid expression \"{expression}\" for derived variable {variable_name}")
+        })?;
+        let is_variable = |name: &str| {
+            self.variables()
+                .any(|variable| variable.template().name() == name)
+        };
+        if !is_variable(variable_name) {
+            bail!(
+                "No variable with name {variable_name} found in experiment {}",
+                self.name
+            );
+        }
+        if let Some(unknown_variable) = expression
+            .variable_names()
+            .into_iter()
+            .find(|name| !is_variable(name))
+        {
+            bail!(
+                "Derived variable {variable_name} references variable {unknown_variable}, which is not a variable of experiment {}",
+                self.name
+            );
+        }
+
+        // The new expression closes a cycle if any variable that it depends on (directly or through other derived
+        // variables) is the variable itself
+        let mut dependencies = expression.variable_names().into_iter().collect::<Vec<_>>();
+        let mut visited = HashSet::new();
+        while let Some(dependency) = dependencies.pop() {
+            if dependency == variable_name {
+                bail!("Derived variable {variable_name} depends on itself");
+            }
+            if visited.insert(dependency) {
+                if let Some(dependency_expression) = self.derived_variables.get(dependency) {
+                    dependencies.extend(dependency_expression.variable_names());
+                }
+            }
+        }
+
+        self.derived_variables
+            .insert(variable_name.to_owned(), expression);
+        Ok(())
+    }
+
+    /// Returns `true` if the variable with the given name is derived from other variables of this experiment (see
+    /// `set_variable_derived`)
+    pub fn is_variable_derived(&self, variable_name: &str) -> bool {
+        self.derived_variables.contains_key(variable_name)
+    }
+
+    /// Computes the values of all derived variables from the `measured_variables` and adds them to the measured values.
+    /// Derived variables whose expression references a variable without a value are added to `missing_variables`
+    fn add_derived_values<'e>(
+        &'e self,
+        measured_variables: &mut HashMap<&'e Variable, String>,
+        missing_variables: &mut HashSet<&'e Variable>,
+    ) -> Result<()> {
+        let mut pending_variables = self
+            .required_variables
+            .iter()
+            .filter(|variable| self.is_variable_derived(variable.template().name()))
+            .collect::<Vec<_>>();
+        if let Some(measured_variable) = pending_variables
+            .iter()
+            .find(|variable| measured_variables.contains_key(*variable))
+        {
+            bail!(
+                "Variable {} is derived from other variables, so its value can't be added to a run",
+                measured_variable.template().name()
+            );
+        }
+
+        // Derived variables can reference each other, so they are computed once the values of all referenced derived
+        // variables are known. There are no cycles, so every iteration computes at least one value
+        while !pending_variables.is_empty() {
+            let is_pending = |name: &str| {
+                pending_variables
+                    .iter()
+                    .any(|variable| variable.template().name() == name)
+            };
+            let (ready_variables, still_pending_variables) = pending_variables
+                .iter()
+                .copied()
+                .partition::<Vec<&Variable>, _>(|variable| {
+                    !self.derived_variables[variable.template().name()]
+                        .variable_names()
+                        .into_iter()
+                        .any(is_pending)
+                });
+
+            for variable in ready_variables {
+                let name = variable.template().name();
+                let expression = &self.derived_variables[name];
+                let mut values = HashMap::new();
+                for dependency in expression.variable_names() {
+                    let Some(value) = measured_variables
+                        .iter()
+                        .find(|(variable, _)| variable.template().name() == dependency)
+                        .map(|(_, value)| value)
+                    else {
+                        continue;
+                    };
+                    let number = parse_numeric_value(value)
+                        .ok_or_else(|| ExarError::NotANumber {
+                            variable: dependency.to_owned(),
+                            value: value.clone(),
+                        })
+                        .with_context(|| format!("Derived variable {name} requires a number"))?;
+                    values.insert(dependency, number);
+                }
+                // Variables without a value make the derived value missing instead of failing the run
+                if values.len() < expression.variable_names().len() {
+                    missing_variables.insert(variable);
+                    continue;
+                }
+                let value = expression.evaluate(&|dependency| Ok(values[dependency]))?;
+                if !value.is_finite() {
+                    bail!("Derived variable {name} has the non-finite value {value}, e.g. because of a division by zero");
+                }
+                measured_variables.insert(variable, value.to_string());
+            }
+            pending_variables = still_pending_variables;
+        }
+        Ok(())
+    }
+
     /// Set the autologging feature to active or inactive. If active, every experiment run will be logged
     /// using the `log` crate. By default, autologging is disabled
     pub fn set_autolog_runs(&mut self, autolog_runs: bool) {
         self.autolog_runs = autolog_runs;
     }
 
+    /// Sets a file to which `run` appends runs if no connection to the database can be established, so that no data
+    /// is lost while the database is unreachable. Runs are stored as one JSON object per line and can be inserted into
+    /// the database later on using `replay_fallback_file`. Pass `None` to disable the fallback (the default)
+    pub fn set_fallback_file(&mut self, fallback_file: Option<PathBuf>) {
+        self.fallback_file = fallback_file;
+    }
+
+    /// Renames this experiment in the database. Since all data of the experiment references it by its ID,
+    /// only the name itself has to change. Fails if another experiment with `new_name` already exists
+    pub fn rename(&mut self, new_name: &str) -> Result<()> {
+        let mut client = connect().context("Failed to connect to DB")?;
+
+        let mut transaction = client
+            .transaction()
+            .context("Failed to begin transaction")?;
+
+        let existing = transaction
+            .query_opt("SELECT id FROM experiments WHERE name = $1;", &[&new_name])
+            .context("Failed to query experiments by name")?;
+        if let Some(existing) = existing {
+            let existing_id: &str = existing.get(0);
+            if existing_id != self.id {
+                bail!("An experiment with name \"{new_name}\" already exists");
+            }
+        }
+
+        let updated_rows = execute(
+            &mut transaction,
+            "UPDATE experiments SET name = $1 WHERE id = $2;",
+            &[&new_name, &self.id],
+        )
+        .context("Failed to update experiment name")?;
+        if updated_rows != 1 {
+            bail!("Experiment {} does not exist in the database", self.id);
+        }
+
+        commit(transaction).context("Failed to commit transaction")?;
+
+        self.name = new_name.to_owned();
+        Ok(())
+    }
+
+    /// Adds `tag` (e.g. "baseline") to this experiment, so that it can be found through `Experiment::with_tag`. Adding a
+    /// tag that the experiment already has does nothing
+    pub fn add_tag(&self, tag: &str) -> Result<()> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        let mut transaction = client
+            .transaction()
+            .context("Failed to begin transaction")?;
+        execute(
+            &mut transaction,
+            "INSERT INTO experiment_tags (experiment_id, tag) VALUES ($1, $2) ON CONFLICT DO NOTHING;",
+            &[&self.id, &tag],
+        )
+        .context("Failed to insert experiment tag")?;
+        commit(transaction).context("Failed to commit transaction")?;
+        Ok(())
+    }
+
+    /// Removes `tag` from this experiment. Returns `false` if the experiment did not have the tag
+    pub fn remove_tag(&self, tag: &str) -> Result<bool> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        let mut transaction = client
+            .transaction()
+            .context("Failed to begin transaction")?;
+        let deleted_rows = execute(
+            &mut transaction,
+            "DELETE FROM experiment_tags WHERE experiment_id = $1 AND tag = $2;",
+            &[&self.id, &tag],
+        )
+        .context("Failed to delete experiment tag")?;
+        commit(transaction).context("Failed to commit transaction")?;
+        Ok(deleted_rows > 0)
+    }
+
+    /// Fetches all tags of this experiment from the database
+    pub fn tags(&self) -> Result<BTreeSet<String>> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        let rows = client
+            .query(
+                "SELECT tag FROM experiment_tags WHERE experiment_id = $1;",
+                &[&self.id],
+            )
+            .context("Failed to fetch experiment tags")?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
     /// Deletes this experiment and all associated data from the database. This function is not undoable, so
     /// be very careful when calling it!
     pub fn delete_from_database(self) -> Result<()> {
@@ -345,37 +1322,45 @@ impl Experiment {
             .context("Failed to begin transaction")?;
 
         // delete all measurements for the experiment
-        transaction
-            .execute(
-                "DELETE FROM measurements WHERE experimentid = $1;",
-                &[&self.id],
-            )
-            .context("Failed to delete measurements")?;
+        execute(
+            &mut transaction,
+            "DELETE FROM measurements WHERE experimentid = $1;",
+            &[&self.id],
+        )
+        .context("Failed to delete measurements")?;
 
         // delete all runs for the experiment
-        transaction
-            .execute(
-                "DELETE FROM experiment_runs WHERE experimentid = $1;",
-                &[&self.id],
-            )
-            .context("Failed to delete experiment runs")?;
+        execute(
+            &mut transaction,
+            "DELETE FROM experiment_runs WHERE experimentid = $1;",
+            &[&self.id],
+        )
+        .context("Failed to delete experiment runs")?;
+
+        execute(
+            &mut transaction,
+            "DELETE FROM experiment_tags WHERE experiment_id = $1;",
+            &[&self.id],
+        )
+        .context("Failed to delete experiment tags")?;
 
         // delete all experiment_variables entries
-        transaction
-            .execute(
-                "DELETE FROM experiment_variables WHERE experiment_id = $1;",
-                &[&self.id],
-            )
-            .context("Failed to delete experiment variables")?;
+        execute(
+            &mut transaction,
+            "DELETE FROM experiment_variables WHERE experiment_id = $1;",
+            &[&self.id],
+        )
+        .context("Failed to delete experiment variables")?;
 
         // Delete the experiment itself
-        transaction
-            .execute("DELETE FROM experiments WHERE id = $1;", &[&self.id])
-            .context("Failed to delete experiment")?;
+        execute(
+            &mut transaction,
+            "DELETE FROM experiments WHERE id = $1;",
+            &[&self.id],
+        )
+        .context("Failed to delete experiment")?;
 
-        transaction
-            .commit()
-            .context("Failed to commit transaction")?;
+        commit(transaction).context("Failed to commit transaction")?;
 
         Ok(())
     }
@@ -390,7 +1375,7 @@ impl Experiment {
 
         let matching_runs = run_numbers
             .map(|run_number| {
-                RawRun::from_run_number_and_experiment(run_number, &self, &mut client).and_then(
+                RawRun::from_run_number_and_experiment(run_number, self, &mut *client).and_then(
                     |maybe_run| {
                         maybe_run.ok_or(anyhow!("No run found with run number {run_number}"))
                     },
@@ -407,13 +1392,284 @@ impl Experiment {
             run.delete_from_database(&mut transaction)?;
         }
 
-        transaction
-            .commit()
-            .context("Failed to commit transaction for deleting runs")?;
+        commit(transaction).context("Failed to commit transaction for deleting runs")?;
 
         Ok(())
     }
 
+    /// Deletes all runs of this experiment whose timestamp lies within `range` (e.g. to prune old runs), together with
+    /// their measurements. Returns the number of deleted runs. This function is not undoable, so be very careful when
+    /// calling it!
+    pub fn delete_runs_in_date_range(&self, range: Range<SystemTime>) -> Result<usize> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        let mut transaction = client
+            .transaction()
+            .context("Failed to begin transaction")?;
+
+        // Measurements reference their runs, so they have to be deleted first
+        execute(
+            &mut transaction,
+            "DELETE FROM measurements WHERE runid IN (SELECT id FROM experiment_runs WHERE experimentid = $1 AND timestamp >= $2 AND timestamp < $3)",
+            &[&self.id, &range.start, &range.end],
+        )
+        .context("Failed to delete measurements")?;
+        let deleted_runs = execute(
+            &mut transaction,
+            "DELETE FROM experiment_runs WHERE experimentid = $1 AND timestamp >= $2 AND timestamp < $3",
+            &[&self.id, &range.start, &range.end],
+        )
+        .context("Failed to delete experiment runs")?;
+
+        commit(transaction).context("Failed to commit transaction for deleting runs")?;
+
+        Ok(deleted_runs as usize)
+    }
+
+    /// Returns a JSON Schema (draft 2020-12) that describes the values of a run of this experiment, as a contract for
+    /// external tools. The schema is an object with one property per variable, containing the description of the
+    /// variable and its unit as `x-unit`. Variables with a well-known unit (see `base_unit`) and derived variables have
+    /// type `number`, all others type `string`. Derived variables are computed when a run is stored, so they are marked as
+    /// `readOnly`. All variables that are neither optional nor derived are required
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        let mut variables = self.variables().collect::<Vec<_>>();
+        variables.sort_by(|a, b| a.template().name().cmp(b.template().name()));
+
+        let properties = variables
+            .iter()
+            .map(|variable| {
+                let template = variable.template();
+                let is_derived = self.is_variable_derived(template.name());
+                let json_type = if is_derived || base_unit(template.unit()).is_some() {
+                    "number"
+                } else {
+                    "string"
+                };
+                let mut property = serde_json::json!({ "type": json_type });
+                if !template.description().is_empty() {
+                    property["description"] = template.description().into();
+                }
+                if !template.unit().is_empty() {
+                    property["x-unit"] = template.unit().into();
+                }
+                if is_derived {
+                    property["readOnly"] = true.into();
+                }
+                (template.name().to_owned(), property)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        let required = variables
+            .iter()
+            .map(|variable| variable.template().name())
+            .filter(|name| !self.is_variable_optional(name) && !self.is_variable_derived(name))
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": self.name,
+            "description": self.description,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+
+    /// Fetches this experiment together with all of its runs and tags from the database, e.g. to move the experiment
+    /// to another database through `Experiment::import_archive`
+    pub fn to_archive(&self) -> Result<ExperimentArchive> {
+        let mut variables = self
+            .variables()
+            .map(|variable| ArchivedVariable {
+                id: variable.id().to_owned(),
+                name: variable.template().name().to_owned(),
+                description: variable.template().description().to_owned(),
+                unit: variable.template().unit().to_owned(),
+            })
+            .collect::<Vec<_>>();
+        variables.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // Runs without any measurements are not returned by `all_runs`, so the run headers determine which runs exist
+        let mut measurements_per_run = self
+            .all_runs()
+            .context("Failed to fetch runs of experiment")?
+            .into_iter()
+            .map(|run| {
+                let measurements = run
+                    .measurements()
+                    .iter()
+                    .map(|measurement| {
+                        (
+                            measurement.variable().template().name().to_owned(),
+                            measurement.value().map(str::to_owned),
+                        )
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                (run.id().to_owned(), measurements)
+            })
+            .collect::<HashMap<_, _>>();
+        let runs = self
+            .run_headers()
+            .context("Failed to fetch runs of experiment")?
+            .into_iter()
+            .map(|raw_run| ArchivedRun {
+                measurements: measurements_per_run
+                    .remove(&raw_run.run_id)
+                    .unwrap_or_default(),
+                id: raw_run.run_id,
+                run_number: raw_run.run_number,
+                label: raw_run.label,
+                timestamp: raw_run.timestamp,
+            })
+            .collect();
+
+        Ok(ExperimentArchive {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            researcher: self.researcher.clone(),
+            variables,
+            tags: self.tags().context("Failed to fetch tags of experiment")?,
+            runs,
+        })
+    }
+
+    /// Inserts the experiment from `archive` (see `Experiment::to_archive`) with all of its runs and tags into the
+    /// database within a single transaction. Fails if an experiment with the same name already exists. The IDs of the
+    /// experiment and its runs as well as the run numbers are preserved, unless another experiment or run with the same
+    /// ID already exists, in which case a new ID is generated. Variables are matched by name, just as in
+    /// `Experiment::new`
+    pub fn import_archive(archive: &ExperimentArchive) -> Result<Self> {
+        let templates = archive
+            .variables
+            .iter()
+            .map(|variable| {
+                VariableTemplate::new(
+                    variable.name.clone().into(),
+                    variable.description.clone().into(),
+                    variable.unit.clone().into(),
+                )
+            })
+            .collect::<HashSet<_>>();
+        Self::ensure_unique_variable_names(&templates)?;
+
+        let mut db_client =
+            crate::postgres::connect().context("Could not connect to postgres DB")?;
+        let mut transaction = db_client.transaction().context("Can't start transaction")?;
+
+        let name_is_taken = transaction
+            .query_opt(
+                "SELECT id FROM experiments WHERE name = $1",
+                &[&archive.name],
+            )
+            .context("Failed to query experiments by name")?
+            .is_some();
+        if name_is_taken {
+            bail!(
+                "An experiment with name \"{}\" already exists",
+                archive.name
+            );
+        }
+
+        let variables = templates
+            .into_iter()
+            .map(|template| -> Result<Variable> {
+                match template
+                    .fetch_from_db(&mut transaction)
+                    .context("Failed to check database for existing variable")?
+                {
+                    Some(variable) => Ok(variable),
+                    None => template.insert_into_db(&mut transaction),
+                }
+            })
+            .collect::<Result<HashSet<_>>>()
+            .context("Inserting variables into database failed")?;
+
+        let id_is_taken = transaction
+            .query_opt("SELECT id FROM experiments WHERE id = $1", &[&archive.id])
+            .context("Failed to query experiments by ID")?
+            .is_some();
+        let experiment_id = if id_is_taken {
+            gen_unused_id("experiments", &mut transaction)?
+        } else {
+            archive.id.clone()
+        };
+        Self::insert_new_experiment_into_db(
+            &experiment_id,
+            &archive.name,
+            &archive.description,
+            &archive.researcher,
+            &variables,
+            &mut transaction,
+        )
+        .context("Failed to insert new experiment into database")?;
+
+        let experiment = Self {
+            id: experiment_id,
+            name: archive.name.clone(),
+            description: archive.description.clone(),
+            researcher: archive.researcher.clone(),
+            required_variables: variables,
+            optional_variables: Default::default(),
+            derived_variables: Default::default(),
+            autolog_runs: false,
+            fallback_file: None,
+        };
+
+        for tag in &archive.tags {
+            execute(
+                &mut transaction,
+                "INSERT INTO experiment_tags (experiment_id, tag) VALUES ($1, $2)",
+                &[&experiment.id, tag],
+            )
+            .context("Failed to insert experiment tag")?;
+        }
+
+        for run in &archive.runs {
+            if let Some(unknown_variable) = run.measurements.keys().find(|name| {
+                !experiment
+                    .variables()
+                    .any(|variable| variable.template().name() == name.as_str())
+            }) {
+                bail!(
+                    "Run {} has a measurement for variable {unknown_variable} which is not a variable of the experiment",
+                    run.id
+                );
+            }
+
+            let run_id_is_taken = RawRun::from_id(&run.id, &mut transaction)
+                .context("Failed to query experiment runs by ID")?
+                .is_some();
+            let run_id = if run_id_is_taken {
+                gen_unused_id("experiment_runs", &mut transaction)?
+            } else {
+                run.id.clone()
+            };
+            experiment
+                .insert_run(
+                    &run_id,
+                    run.run_number as i32,
+                    run.label.as_deref(),
+                    run.timestamp,
+                    &mut transaction,
+                )
+                .with_context(|| format!("Failed to insert run {}", run.id))?;
+            for variable in experiment.variables() {
+                let value = run
+                    .measurements
+                    .get(variable.template().name())
+                    .cloned()
+                    .flatten();
+                experiment
+                    .insert_measurement(variable, &run_id, value, run.timestamp, &mut transaction)
+                    .with_context(|| format!("Failed to insert measurement of run {}", run.id))?;
+            }
+        }
+
+        commit(transaction).context("Failed to commit transaction for imported experiment")?;
+
+        Ok(experiment)
+    }
+
     /// Fetches all experiments from the database
     pub fn all() -> Result<Vec<Experiment>> {
         let mut connection = connect().context("Failed to connect to database")?;
@@ -425,16 +1681,63 @@ impl Experiment {
             .into_iter()
             .map(|id| {
                 let id = id.get(0);
-                Self::get_experiment_from_db_by_id(id, &mut connection).and_then(|maybe_experiment| maybe_experiment.ok_or(anyhow!("Experiment with ID {} not found in database, even though the ID exists", id)))
+                Self::get_experiment_from_db_by_id(id, &mut *connection).and_then(|maybe_experiment| maybe_experiment.ok_or(anyhow!("Experiment with ID {} not found in database, even though the ID exists", id)))
+            })
+            .collect()
+    }
+
+    /// Fetches all experiments that have the given tag (see `add_tag`) from the database, sorted by name
+    pub fn with_tag(tag: &str) -> Result<Vec<Experiment>> {
+        let mut connection = connect().context("Failed to connect to database")?;
+        let experiment_ids = connection
+            .query(
+                "SELECT experiments.id FROM experiments INNER JOIN experiment_tags ON experiment_tags.experiment_id = experiments.id WHERE experiment_tags.tag = $1 ORDER BY experiments.name",
+                &[&tag],
+            )
+            .context("Failed to fetch IDs of tagged experiments")?;
+
+        experiment_ids
+            .into_iter()
+            .map(|id| {
+                let id: &str = id.get(0);
+                Self::get_experiment_from_db_by_id(id, &mut *connection).and_then(
+                    |maybe_experiment| {
+                        maybe_experiment.ok_or(anyhow!(
+                        "Experiment with ID {} not found in database, even though the ID exists",
+                        id
+                    ))
+                    },
+                )
             })
             .collect()
     }
 
+    /// Fails if two of the given `variables` have the same name. Variables are looked up by name (e.g. in
+    /// `RunContext::add_value_by_name`), so two variables with the same name but different descriptions or units would
+    /// be ambiguous
+    fn ensure_unique_variable_names(variables: &HashSet<VariableTemplate>) -> Result<()> {
+        let mut names = HashSet::new();
+        let mut duplicates = variables
+            .iter()
+            .map(|variable| variable.name())
+            .filter(|name| !names.insert(*name))
+            .collect::<Vec<_>>();
+        if !duplicates.is_empty() {
+            duplicates.sort_unstable();
+            duplicates.dedup();
+            bail!(
+                "Variable names must be unique within an experiment, but found multiple variables named {}",
+                duplicates.join(", ")
+            );
+        }
+        Ok(())
+    }
+
     /// Queries the database for an experiment with the same name to determine whether this is a new
     /// experiment or a known experiment
-    fn get_experiment_from_db_by_name(
+    fn get_experiment_from_db_by_name<C: GenericClient>(
         name: &str,
-        client: &mut Client,
+        client: &mut C,
     ) -> Result<Option<Experiment>> {
         let rows = client
             .query("SELECT * FROM experiments WHERE name = $1", &[&name])
@@ -446,15 +1749,26 @@ impl Experiment {
                 let row = &rows[0];
                 let id: String = row.get("id");
 
-                let variables = Self::query_variables_for_experiment(&id, client).context("Failed to query variables for experiment")?;
+                let variables = Self::query_variables_for_experiment(&id, client)
+                    .context("Failed to query variables for experiment")?;
 
-                Ok(Some(Experiment { id, name: name.to_owned(), description: row.get("description"), researcher: row.get("researcher"), required_variables: variables, autolog_runs: false, }))
-            },
+                Ok(Some(Experiment {
+                    id,
+                    name: name.to_owned(),
+                    description: row.get("description"),
+                    researcher: row.get("researcher"),
+                    required_variables: variables,
+                    optional_variables: Default::default(),
+                    derived_variables: Default::default(),
+                    autolog_runs: false,
+                    fallback_file: None,
+                }))
+            }
             _ => panic!("Found more than one experiment with the same name, but experiment names have to be unique!"),
         }
     }
 
-    fn get_experiment_from_db_by_id<C: GenericClient>(
+    pub(crate) fn get_experiment_from_db_by_id<C: GenericClient>(
         id: &str,
         client: &mut C,
     ) -> Result<Option<Experiment>> {
@@ -477,7 +1791,10 @@ impl Experiment {
                     description: row.get("description"),
                     researcher: row.get("researcher"),
                     required_variables: variables,
+                    optional_variables: Default::default(),
+                    derived_variables: Default::default(),
                     autolog_runs: false,
+                    fallback_file: None,
                 }))
             }
             _ => bail!(
@@ -517,32 +1834,57 @@ impl Experiment {
             .collect()
     }
 
-    /// Inserts a new experiment into the database and returns the ID for this new experiment
+    /// Inserts a new experiment with the given `id` and its relations to `variables` into the database
     fn insert_new_experiment_into_db<C: GenericClient>(
+        id: &str,
         name: &str,
         description: &str,
         researcher: &str,
         variables: &HashSet<Variable>,
         client: &mut C,
-    ) -> Result<String> {
-        let id = gen_unique_id();
-        let changed_rows = client
-            .execute(
-                "INSERT INTO experiments VALUES ($1, $2, $3, $4)",
-                &[&id, &researcher, &name, &description],
-            )
-            .context("Failed to execute query")?;
+    ) -> Result<()> {
+        let changed_rows = execute(
+            client,
+            "INSERT INTO experiments VALUES ($1, $2, $3, $4)",
+            &[&id, &researcher, &name, &description],
+        )
+        .context("Failed to execute query")?;
 
         if changed_rows != 1 {
             bail!("Unexpected number of affected rows. Expected 1 but got {changed_rows}");
         }
 
         for variable in variables {
-            Self::insert_experiment_variable_relation(&id, variable.id(), client)
+            Self::insert_experiment_variable_relation(id, variable.id(), client)
                 .context("Failed to insert experiment/variable relation")?;
         }
 
-        Ok(id)
+        Ok(())
+    }
+
+    /// Returns the variables for the given templates, inserting all variables that don't exist in the database yet
+    fn fetch_or_insert_variables<C: GenericClient>(
+        templates: HashSet<VariableTemplate>,
+        client: &mut C,
+    ) -> Result<HashSet<Variable>> {
+        // Concurrent transactions that insert the same new variables have to wait for each other. Inserting in a fixed
+        // order prevents them from deadlocking
+        let mut templates = templates.into_iter().collect::<Vec<_>>();
+        templates.sort_by(|a, b| a.name().cmp(b.name()));
+        templates
+            .into_iter()
+            .map(|variable| -> Result<Variable> {
+                // Check if we have a matching variable in the DB!
+                let matching_variable = variable
+                    .fetch_from_db(client)
+                    .context("Failed to check database for existing variable")?;
+                if let Some(variable) = matching_variable {
+                    Ok(variable)
+                } else {
+                    variable.insert_into_db(client)
+                }
+            })
+            .collect()
     }
 
     fn insert_experiment_variable_relation<C: GenericClient>(
@@ -550,12 +1892,12 @@ impl Experiment {
         variable_id: &str,
         client: &mut C,
     ) -> Result<()> {
-        let changed_rows = client
-            .execute(
-                "INSERT INTO experiment_variables VALUES ($1, $2)",
-                &[&experiment_id, &variable_id],
-            )
-            .context("Failed to execute INSERT statement for experiment_variables table")?;
+        let changed_rows = execute(
+            client,
+            "INSERT INTO experiment_variables VALUES ($1, $2)",
+            &[&experiment_id, &variable_id],
+        )
+        .context("Failed to execute INSERT statement for experiment_variables table")?;
 
         if changed_rows != 1 {
             bail!("Unexpected number of affected rows. Expected 1 but got {changed_rows}");
@@ -564,9 +1906,152 @@ impl Experiment {
         Ok(())
     }
 
+    /// Executes the experiment function `func` and returns the values that it measured, together with the label of the
+    /// run (if any). Returns an error if `func` did not measure a value for every required variable
+    fn execute_run_function<F: FnOnce(&RunContext) -> Result<()>>(
+        &self,
+        func: F,
+    ) -> Result<(HashMap<&Variable, String>, Option<String>)> {
+        let context = RunContext::from_experiment(self);
+        func(&context).context("Experiment function failed")?;
+        context.into_measured_values()
+    }
+
+    /// Returns an error if `measured_variables` is missing a value for any variable of this experiment that is not
+    /// optional and not part of `explicitly_missing_variables`
+    fn ensure_required_variables_are_measured(
+        &self,
+        measured_variables: &HashMap<&Variable, String>,
+        explicitly_missing_variables: &HashSet<&Variable>,
+    ) -> Result<()> {
+        let missing_variables = self
+            .required_variables
+            .iter()
+            .filter(|variable| {
+                !measured_variables.contains_key(variable)
+                    && !explicitly_missing_variables.contains(variable)
+                    && !self.is_variable_optional(variable.template().name())
+            })
+            .map(|variable| variable.template().name().to_owned())
+            .collect::<Vec<_>>();
+        if !missing_variables.is_empty() {
+            return Err(ExarError::MissingValues(missing_variables).into());
+        }
+        Ok(())
+    }
+
+    /// Inserts rows into `table` using multi-row INSERT statements. `params` contains the values of all rows, with
+    /// `columns` values per row. Large numbers of rows are split into multiple statements to stay below the parameter
+    /// limit of postgres
+    fn insert_rows<C: GenericClient>(
+        table: &str,
+        columns: usize,
+        params: &[&(dyn ToSql + Sync)],
+        client: &mut C,
+    ) -> Result<()> {
+        ensure_schema_table(table)?;
+        for chunk in params.chunks(MAX_ROWS_PER_INSERT * columns) {
+            let rows = chunk.len() / columns;
+            let placeholders = (0..rows)
+                .map(|row| {
+                    let row_placeholders = (1..=columns)
+                        .map(|column| format!("${}", row * columns + column))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("({row_placeholders})")
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let changed_rows = execute(
+                client,
+                &format!("INSERT INTO {table} VALUES {placeholders}"),
+                chunk,
+            )
+            .with_context(|| format!("Failed to execute INSERT statement for table {table}"))?;
+            if changed_rows != rows as u64 {
+                bail!("Unexpected number of affected rows. Expected {rows} but got {changed_rows}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Connects to the DB and inserts a new run with the given measured values in a single transaction. Returns the ID
+    /// and the run number of the new run
+    fn store_run(
+        &self,
+        measured_variables: &HashMap<&Variable, String>,
+        label: Option<&str>,
+        timestamp: SystemTime,
+    ) -> Result<(String, i32)> {
+        let mut db_client =
+            crate::postgres::connect().context("Could not connect to postgres DB")?;
+        let mut transaction = db_client.transaction().context("Can't start transaction")?;
+        let run_id = gen_unused_id("experiment_runs", &mut transaction)?;
+        let run_number = self
+            .insert_run_with_measurements(
+                measured_variables,
+                &run_id,
+                label,
+                timestamp,
+                &mut transaction,
+            )
+            .context("Failed to insert new experiment run into the database")?;
+        commit(transaction)
+            .context("Failed to commit transaction for inserting result of experiment run")?;
+        Ok((run_id, run_number))
+    }
+
+    /// Inserts a new run with the given ID and measured values into the DB. Returns the run number of the new run
+    pub(crate) fn insert_run_with_measurements<C: GenericClient>(
+        &self,
+        measured_variables: &HashMap<&Variable, String>,
+        run_id: &str,
+        label: Option<&str>,
+        timestamp: SystemTime,
+        client: &mut C,
+    ) -> Result<i32> {
+        if let Some(label) = label {
+            let existing_run = client
+                .query_opt(
+                    "SELECT id FROM experiment_runs WHERE experimentid = $1 AND label = $2",
+                    &[&self.id, &label],
+                )
+                .context("Failed to query experiment_runs table")?;
+            if let Some(existing_run) = existing_run {
+                let existing_run_id: &str = existing_run.get(0);
+                bail!(
+                    "Experiment {} already has a run with label \"{label}\" (run {existing_run_id})",
+                    self.name
+                );
+            }
+        }
+
+        let run_number = self
+            .get_current_run_number_from_db(client)
+            .context("Can't get run number of previous run of this experiment")?
+            .unwrap_or(0)
+            + 1;
+
+        // Insert a new run and one measurement for each variable
+        self.insert_run(run_id, run_number, label, timestamp, client)
+            .context("Failed to insert new experiment run into the database")?;
+        // Optional variables without a value are stored as NULL measurements, so that there is still one
+        // measurement per variable and run
+        for variable in &self.required_variables {
+            let value = measured_variables.get(variable).cloned();
+            self.insert_measurement(variable, run_id, value, timestamp, client)
+                .context("Failed to insert new measurement")?;
+        }
+
+        Ok(run_number)
+    }
+
     /// Returns the number of the last run of this experiment from the DB. If this experiment has never been run,
     /// `None` is returned
-    fn get_current_run_number_from_db(&self, client: &mut Client) -> Result<Option<i32>> {
+    fn get_current_run_number_from_db<C: GenericClient>(
+        &self,
+        client: &mut C,
+    ) -> Result<Option<i32>> {
         let results = client.query("SELECT runnumber FROM experiment_runs WHERE experimentid = $1 ORDER BY runnumber DESC LIMIT 1", &[&self.id]).context("Failed to query experiment_runs table")?;
 
         match results.len() {
@@ -583,40 +2068,52 @@ impl Experiment {
     }
 
     /// Inserts a new experiment run into the DB
-    fn insert_run<C: GenericClient>(&self, run_number: i32, client: &mut C) -> Result<String> {
-        let run_id = gen_unique_id();
-        let timestamp = SystemTime::now();
-
-        let changed_rows = client
-            .execute(
-                "INSERT INTO experiment_runs VALUES ($1, $2, $3, $4)",
-                &[&run_number, &self.id, &run_id, &timestamp],
-            )
-            .context("Failed to execute INSERT statement for table experiment_runs")?;
+    fn insert_run<C: GenericClient>(
+        &self,
+        run_id: &str,
+        run_number: i32,
+        label: Option<&str>,
+        timestamp: SystemTime,
+        client: &mut C,
+    ) -> Result<()> {
+        let changed_rows = execute(
+            client,
+            "INSERT INTO experiment_runs VALUES ($1, $2, $3, $4, $5)",
+            &[&run_number, &self.id, &run_id, &timestamp, &label],
+        )
+        .context("Failed to execute INSERT statement for table experiment_runs")?;
 
         if changed_rows != 1 {
             bail!("Unexpected number of affected rows. Expected 1 but got {changed_rows}");
         }
 
-        Ok(run_id)
+        Ok(())
     }
 
     fn insert_measurement<C: GenericClient>(
         &self,
         variable: &Variable,
         run_id: &str,
-        value: String,
+        value: Option<String>,
+        timestamp: SystemTime,
         client: &mut C,
     ) -> Result<String> {
         let id = gen_unique_id();
-        let timestamp = SystemTime::now();
-
-        let changed_rows = client
-            .execute(
-                "INSERT INTO measurements VALUES ($1, $2, $3, $4, $5)",
-                &[&self.id, &variable.id(), &run_id, &value, &timestamp],
-            )
-            .context("Failed to execute INSERT statement for table measurements")?;
+        let numeric_value = value.as_deref().and_then(parse_numeric_value);
+
+        let changed_rows = execute(
+            client,
+            "INSERT INTO measurements VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &self.id,
+                &variable.id(),
+                &run_id,
+                &value,
+                &timestamp,
+                &numeric_value,
+            ],
+        )
+        .context("Failed to execute INSERT statement for table measurements")?;
 
         if changed_rows != 1 {
             bail!("Unexpected number of affected rows. Expected 1 but got {changed_rows}");
@@ -629,8 +2126,8 @@ impl Experiment {
         info!("Run {run_number}:");
 
         let mut table_builder = Builder::default();
-        table_builder.set_header(variables.iter().map(|(var, _)| var.template().name()));
-        table_builder.push_record(variables.iter().map(|(_, value)| value));
+        table_builder.set_header(variables.keys().map(|var| var.template().name()));
+        table_builder.push_record(variables.values());
         let table = table_builder.build();
         info!("{table}");
     }
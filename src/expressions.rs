@@ -0,0 +1,227 @@
+use std::{collections::BTreeSet, iter::Peekable, str::Chars, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+
+/// Binary arithmetic operator of an `Expression`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticOperator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// A simple arithmetic expression over the values of variables, used to compute the value of derived variables (see
+/// `Experiment::set_variable_derived`). Expressions support numbers, variable names, `+`, `-`, `*`, `/`, unary minus
+/// and parentheses, e.g. `Bytes / Runtime` or `(Read + Write) * 2`. Variable names that are not alphanumeric (plus
+/// `_`) have to be quoted, e.g. `"Data size" / Runtime`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    Variable(String),
+    Neg(Box<Expression>),
+    Binary(ArithmeticOperator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Returns the names of all variables that this expression references
+    pub fn variable_names(&self) -> BTreeSet<&str> {
+        let mut names = BTreeSet::new();
+        self.collect_variable_names(&mut names);
+        names
+    }
+
+    fn collect_variable_names<'a>(&'a self, names: &mut BTreeSet<&'a str>) {
+        match self {
+            Self::Number(_) => {}
+            Self::Variable(name) => {
+                names.insert(name);
+            }
+            Self::Neg(operand) => operand.collect_variable_names(names),
+            Self::Binary(_, lhs, rhs) => {
+                lhs.collect_variable_names(names);
+                rhs.collect_variable_names(names);
+            }
+        }
+    }
+
+    /// Evaluates this expression, taking the values of variables from `value_of`
+    pub(crate) fn evaluate<F: Fn(&str) -> Result<f64>>(&self, value_of: &F) -> Result<f64> {
+        match self {
+            Self::Number(number) => Ok(*number),
+            Self::Variable(name) => value_of(name),
+            Self::Neg(operand) => Ok(-operand.evaluate(value_of)?),
+            Self::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(value_of)?;
+                let rhs = rhs.evaluate(value_of)?;
+                Ok(match op {
+                    ArithmeticOperator::Add => lhs + rhs,
+                    ArithmeticOperator::Sub => lhs - rhs,
+                    ArithmeticOperator::Mul => lhs * rhs,
+                    ArithmeticOperator::Div => lhs / rhs,
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: s.chars().peekable(),
+        };
+        let expression = parser.parse_sum()?;
+        parser.skip_whitespace();
+        if let Some(c) = parser.chars.peek() {
+            bail!("Unexpected character '{c}' in expression \"{s}\"");
+        }
+        Ok(expression)
+    }
+}
+
+/// Recursive descent parser for `Expression`s
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression> {
+        let mut expression = self.parse_product()?;
+        loop {
+            self.skip_whitespace();
+            let op = match self.chars.peek() {
+                Some('+') => ArithmeticOperator::Add,
+                Some('-') => ArithmeticOperator::Sub,
+                _ => return Ok(expression),
+            };
+            self.chars.next();
+            let rhs = self.parse_product()?;
+            expression = Expression::Binary(op, Box::new(expression), Box::new(rhs));
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Expression> {
+        let mut expression = self.parse_factor()?;
+        loop {
+            self.skip_whitespace();
+            let op = match self.chars.peek() {
+                Some('*') => ArithmeticOperator::Mul,
+                Some('/') => ArithmeticOperator::Div,
+                _ => return Ok(expression),
+            };
+            self.chars.next();
+            let rhs = self.parse_factor()?;
+            expression = Expression::Binary(op, Box::new(expression), Box::new(rhs));
+        }
+    }
+
+    fn parse_factor(&mut self) -> Result<Expression> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some('-') => {
+                self.chars.next();
+                Ok(Expression::Neg(Box::new(self.parse_factor()?)))
+            }
+            Some('(') => {
+                self.chars.next();
+                let expression = self.parse_sum()?;
+                self.skip_whitespace();
+                if self.chars.next() != Some(')') {
+                    bail!("Missing closing parenthesis");
+                }
+                Ok(expression)
+            }
+            Some('"') => {
+                self.chars.next();
+                let mut name = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some('"') => break,
+                        Some(c) => name.push(c),
+                        None => bail!("Missing closing quote after variable name {name}"),
+                    }
+                }
+                if name.is_empty() {
+                    bail!("Quoted variable names must not be empty");
+                }
+                Ok(Expression::Variable(name))
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                let number = number
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid number {number}"))?;
+                Ok(Expression::Number(number))
+            }
+            Some(c) if c.is_alphanumeric() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                Ok(Expression::Variable(name))
+            }
+            Some(c) => bail!("Unexpected character '{c}'"),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(expression: &str) -> Result<f64> {
+        expression
+            .parse::<Expression>()?
+            .evaluate(&|name| match name {
+                "Bytes" => Ok(3000.0),
+                "Runtime" => Ok(1.5),
+                "Data size" => Ok(10.0),
+                _ => bail!("Unknown variable {name}"),
+            })
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        assert_eq!(2000.0, evaluate("Bytes / Runtime").unwrap());
+        assert_eq!(7.0, evaluate("1 + 2 * 3").unwrap());
+        assert_eq!(9.0, evaluate("(1 + 2) * 3").unwrap());
+        assert_eq!(-1.0, evaluate("2 - 3").unwrap());
+        assert_eq!(-4.0, evaluate("1 - 2 - 3").unwrap());
+        assert_eq!(1.0, evaluate("-Runtime + 2.5").unwrap());
+        assert_eq!(20.0, evaluate("\"Data size\" * 2").unwrap());
+        assert!(evaluate("Unknown + 1").is_err());
+    }
+
+    #[test]
+    fn parse_invalid_expressions() {
+        assert!("".parse::<Expression>().is_err());
+        assert!("Bytes /".parse::<Expression>().is_err());
+        assert!("(Bytes + 1".parse::<Expression>().is_err());
+        assert!("Bytes Runtime".parse::<Expression>().is_err());
+        assert!("1.2.3".parse::<Expression>().is_err());
+        assert!("Bytes % 2".parse::<Expression>().is_err());
+        assert!("\"\" + 1".parse::<Expression>().is_err());
+        assert!("\"Data size * 2".parse::<Expression>().is_err());
+    }
+
+    #[test]
+    fn collect_variable_names() {
+        let expression = "(Read + Write) / Runtime - Read"
+            .parse::<Expression>()
+            .unwrap();
+        assert_eq!(
+            BTreeSet::from(["Read", "Runtime", "Write"]),
+            expression.variable_names()
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{commit, connect, is_dry_run, Experiment, Variable};
+
+/// An experiment run that could not be stored in the database and was written to a fallback file instead. Each line
+/// of a fallback file contains one run as a JSON object
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct FallbackRun {
+    experiment_id: String,
+    experiment_name: String,
+    run_id: String,
+    #[serde(default)]
+    label: Option<String>,
+    timestamp: SystemTime,
+    measurements: BTreeMap<String, String>,
+}
+
+impl FallbackRun {
+    pub(crate) fn new(
+        experiment: &Experiment,
+        run_id: &str,
+        label: Option<&str>,
+        timestamp: SystemTime,
+        measured_variables: &HashMap<&Variable, String>,
+    ) -> Self {
+        Self {
+            experiment_id: experiment.id().to_owned(),
+            experiment_name: experiment.name().to_owned(),
+            run_id: run_id.to_owned(),
+            label: label.map(str::to_owned),
+            timestamp,
+            measurements: measured_variables
+                .iter()
+                .map(|(variable, value)| (variable.template().name().to_owned(), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// Appends this run as a single line to the fallback file at `path`. The file is created if it does not exist
+    pub(crate) fn append_to_file(&self, path: &Path) -> Result<()> {
+        let line = serde_json::to_string(self).context("Failed to serialize run")?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!("Could not open file {}", path.display()))?;
+        writeln!(file, "{line}").context("Failed to write run to file")?;
+        file.sync_all().context("Failed to flush file")?;
+        Ok(())
+    }
+}
+
+fn read_fallback_file(path: &Path) -> Result<Vec<FallbackRun>> {
+    let file = File::open(path).context(format!("Could not open file {}", path.display()))?;
+    let mut runs = vec![];
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.context("Failed to read line from file")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let run = serde_json::from_str(&line)
+            .context(format!("Failed to parse run in line {}", index + 1))?;
+        runs.push(run);
+    }
+    Ok(runs)
+}
+
+/// Inserts all runs from the fallback file at `path` (see `Experiment::set_fallback_file`) into the database and
+/// returns their IDs. All runs are inserted within a single transaction, so either all or none of the runs are stored.
+/// After the runs have been stored, the fallback file is truncated so that replaying it again does not fail. In dry-run
+/// mode (see `is_dry_run`), the file is left unchanged
+pub fn replay_fallback_file<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+    let path = path.as_ref();
+    let runs = read_fallback_file(path).context("Failed to read fallback file")?;
+    if runs.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut db_client = connect().context("Could not connect to postgres DB")?;
+    let mut transaction = db_client.transaction().context("Can't start transaction")?;
+
+    let mut experiments: HashMap<String, Experiment> = HashMap::new();
+    for run in &runs {
+        if !experiments.contains_key(&run.experiment_id) {
+            let experiment =
+                Experiment::get_experiment_from_db_by_id(&run.experiment_id, &mut transaction)
+                    .context("Failed to fetch experiment from DB")?
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Experiment {} (ID {}) of run {} does not exist in the database",
+                            run.experiment_name,
+                            run.experiment_id,
+                            run.run_id
+                        )
+                    })?;
+            experiments.insert(run.experiment_id.clone(), experiment);
+        }
+        let experiment = &experiments[&run.experiment_id];
+
+        let measured_variables = run
+            .measurements
+            .iter()
+            .map(|(name, value)| -> Result<(&Variable, String)> {
+                let variable = experiment
+                    .variables()
+                    .find(|variable| variable.template().name() == name)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Experiment {} has no variable named {name}",
+                            experiment.name()
+                        )
+                    })?;
+                Ok((variable, value.clone()))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        experiment
+            .insert_run_with_measurements(
+                &measured_variables,
+                &run.run_id,
+                run.label.as_deref(),
+                run.timestamp,
+                &mut transaction,
+            )
+            .context(format!(
+                "Failed to insert run {} into the database",
+                run.run_id
+            ))?;
+    }
+
+    commit(transaction).context("Failed to commit transaction for replayed runs")?;
+
+    // In dry-run mode the runs were not stored, so they have to stay in the fallback file
+    if is_dry_run() {
+        return Ok(runs.into_iter().map(|run| run.run_id).collect());
+    }
+    File::create(path)
+        .context("Runs were stored in the database, but truncating the fallback file failed")?;
+
+    Ok(runs.into_iter().map(|run| run.run_id).collect())
+}
@@ -2,16 +2,37 @@ mod experiment;
 pub use self::experiment::*;
 
 mod postgres;
-pub(crate) use self::postgres::*;
+pub use self::postgres::*;
 
 mod variables;
 pub use self::variables::*;
 
 mod util;
-pub(crate) use self::util::*;
+pub use self::util::*;
 
 mod measurement;
 pub use self::measurement::*;
 
 mod runs;
 pub use self::runs::*;
+
+mod units;
+pub use self::units::*;
+
+mod stats;
+pub use self::stats::*;
+
+mod fallback;
+pub use self::fallback::*;
+
+mod archive;
+pub use self::archive::*;
+
+mod predicates;
+pub use self::predicates::*;
+
+mod expressions;
+pub use self::expressions::*;
+
+mod errors;
+pub use self::errors::*;
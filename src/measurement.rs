@@ -3,13 +3,13 @@ use std::time::SystemTime;
 
 use postgres::GenericClient;
 
-use crate::Variable;
+use crate::{convert_unit, Variable};
 
 /// A single measurement from an experiment and run
 #[derive(Debug)]
 pub struct Measurement<'a> {
     variable: &'a Variable,
-    value: String,
+    value: Option<String>,
     timestamp: SystemTime,
     run_number: i32,
 }
@@ -17,7 +17,7 @@ pub struct Measurement<'a> {
 impl<'a> Measurement<'a> {
     pub(crate) fn new(
         variable: &'a Variable,
-        value: String,
+        value: Option<String>,
         timestamp: SystemTime,
         run_number: i32,
     ) -> Self {
@@ -29,13 +29,14 @@ impl<'a> Measurement<'a> {
         }
     }
 
-    /// Fetch a Measurement from the DB using the given run ID and variable
+    /// Fetch a Measurement from the DB using the given run ID and variable. Returns `Ok(None)` if the run has no
+    /// measurement for the variable
     pub(crate) fn fetch_by_run_and_variable<C: GenericClient>(
         run_id: &str,
         run_number: i32,
         variable: &'a Variable,
         client: &mut C,
-    ) -> Result<Self> {
+    ) -> Result<Option<Self>> {
         let matching_rows = client
             .query(
                 "SELECT * FROM measurements WHERE runid = $1 AND variableid = $2",
@@ -43,26 +44,44 @@ impl<'a> Measurement<'a> {
             )
             .context("Failed to query for measurements")?;
 
-        if matching_rows.len() != 1 {
-            bail!(
-                "Failed to get (unique) measurement from DB. Expected 1 measurement, but got {}",
+        match matching_rows.len() {
+            0 => Ok(None),
+            1 => {
+                let row = &matching_rows[0];
+                Ok(Some(Self {
+                    variable,
+                    value: row.get("value"),
+                    timestamp: row.get("timestamp"),
+                    run_number,
+                }))
+            }
+            _ => bail!(
+                "Failed to get (unique) measurement from DB. Expected at most 1 measurement, but got {}",
                 matching_rows.len()
-            );
+            ),
         }
+    }
 
-        let row = &matching_rows[0];
-
-        Ok(Self {
-            variable,
-            value: row.get("value"),
-            timestamp: row.get("timestamp"),
-            run_number,
-        })
+    /// Access the value of this measurement. Returns `None` if no value was recorded, which is only possible for
+    /// variables that were marked as optional
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
     }
 
-    /// Access the value of this measurement
-    pub fn value(&self) -> &str {
-        &self.value
+    /// Parses the value of this measurement as a number and converts it from the unit of its variable into `unit`, e.g.
+    /// from "ms" into "s" (see `convert_unit`). Returns `Ok(None)` if no value was recorded, and an error if the value is
+    /// not a number or can't be converted into `unit`
+    pub fn value_in_unit(&self, unit: &str) -> Result<Option<f64>> {
+        let Some(value) = self.value() else {
+            return Ok(None);
+        };
+        let number = value.trim().parse::<f64>().with_context(|| {
+            format!(
+                "Value \"{value}\" of variable {} is not a number",
+                self.variable.template().name()
+            )
+        })?;
+        convert_unit(number, self.variable.template().unit(), unit).map(Some)
     }
 
     /// Access the timestamp of this measurement
@@ -1,11 +1,57 @@
-use anyhow::{Context, Result};
-use postgres::{Client, Config, NoTls};
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt::Display,
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use native_tls::{Certificate, TlsConnector};
+use postgres::{
+    config::SslMode, error::SqlState, types::ToSql, Client, Config, GenericClient, NoTls,
+    Transaction,
+};
+use postgres_native_tls::MakeTlsConnector;
+
+use crate::{
+    ignore_poison,
+    predicates::{parse_numeric_value, NUMERIC_VALUE_PATTERN},
+};
 
 const ENV_PSQL_USER: &str = "PSQL_USER";
 const ENV_PSQL_PWD: &str = "PSQL_PWD";
 const ENV_PSQL_HOST: &str = "PSQL_HOST";
 const ENV_PSQL_PORT: &str = "PSQL_PORT";
 const ENV_PSQL_DBNAME: &str = "PSQL_DBNAME";
+const ENV_PSQL_SSLMODE: &str = "PSQL_SSLMODE";
+const ENV_PSQL_SSLROOTCERT: &str = "PSQL_SSLROOTCERT";
+const ENV_PSQL_POOL_SIZE: &str = "PSQL_POOL_SIZE";
+const ENV_PSQL_DRY_RUN: &str = "PSQL_DRY_RUN";
+const ENV_PSQL_CONNECT_RETRIES: &str = "PSQL_CONNECT_RETRIES";
+const ENV_PSQL_CONNECT_BACKOFF_MS: &str = "PSQL_CONNECT_BACKOFF_MS";
+
+/// Default for the maximum number of idle connections that are kept open for reuse
+const DEFAULT_POOL_SIZE: usize = 8;
+/// Timeout for checking whether an idle connection is still usable before reusing it
+const IDLE_CONNECTION_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default delay before the first retry of a failed connection attempt, which doubles with every further retry
+const DEFAULT_CONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Parses the SSL mode from the value of the `PSQL_SSLMODE` environment variable. Supported values are `disable`,
+/// `prefer` and `require`, matching the corresponding `sslmode` values of libpq
+fn parse_ssl_mode(ssl_mode: &str) -> Result<SslMode> {
+    match ssl_mode.trim().to_ascii_lowercase().as_str() {
+        "disable" => Ok(SslMode::Disable),
+        "prefer" => Ok(SslMode::Prefer),
+        "require" => Ok(SslMode::Require),
+        _ => bail!(
+            "Invalid value \"{ssl_mode}\" for {ENV_PSQL_SSLMODE}, expected one of disable, prefer or require"
+        ),
+    }
+}
 
 /// Returns connection config for postgres DB, fetched from environment variables
 pub(crate) fn get_postgres_config() -> Result<Config> {
@@ -40,16 +86,548 @@ pub(crate) fn get_postgres_config() -> Result<Config> {
                 .context("Could not get database name for postgres connection")?
                 .as_str(),
         );
+    // TLS is optional, without PSQL_SSLMODE the connection is unencrypted
+    if let Ok(ssl_mode) = std::env::var(ENV_PSQL_SSLMODE) {
+        config.ssl_mode(parse_ssl_mode(&ssl_mode)?);
+    } else {
+        config.ssl_mode(SslMode::Disable);
+    }
     Ok(config)
 }
 
-/// Connects to the postgres DB and returns a Client
-pub(crate) fn connect() -> Result<Client> {
-    let config =
-        get_postgres_config().context("Can't get connection configuration for postgres DB")?;
-    let client = config.connect(NoTls).context(format!(
-        "Could not connect to postgres DB with config {:?}",
+/// Error that is returned if no connection to the database could be established, either because the connection
+/// configuration is missing or invalid, or because the database is not reachable. Use `anyhow::Error::chain` to check
+/// whether an error was caused by a failed connection
+#[derive(Debug)]
+pub struct ConnectionError(anyhow::Error);
+
+impl Display for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not connect to the database")
+    }
+}
+
+impl std::error::Error for ConnectionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.0.as_ref())
+    }
+}
+
+/// Creates the connector for TLS connections. The system's root certificates are always trusted. If `PSQL_SSLROOTCERT`
+/// is set, all certificates from this PEM file are trusted as well, e.g. the CA of a managed database service
+fn make_tls_connector() -> Result<MakeTlsConnector> {
+    let mut builder = TlsConnector::builder();
+    if let Ok(root_cert_path) = std::env::var(ENV_PSQL_SSLROOTCERT) {
+        let pem = std::fs::read(&root_cert_path).context(format!(
+            "Could not read root certificate file {root_cert_path} (set through {ENV_PSQL_SSLROOTCERT})"
+        ))?;
+        let certificates = Certificate::stack_from_pem(&pem).context(format!(
+            "Root certificate file {root_cert_path} does not contain valid PEM certificates"
+        ))?;
+        for certificate in certificates {
+            builder.add_root_certificate(certificate);
+        }
+    }
+    let connector = builder.build().context("Failed to create TLS connector")?;
+    Ok(MakeTlsConnector::new(connector))
+}
+
+/// Returns the maximum number of idle connections in the connection pool, which is read from the `PSQL_POOL_SIZE`
+/// environment variable. A pool size of zero disables connection reuse
+fn get_pool_size() -> Result<usize> {
+    match std::env::var(ENV_PSQL_POOL_SIZE) {
+        Ok(pool_size) => pool_size.trim().parse().context(format!(
+            "Value of {ENV_PSQL_POOL_SIZE} must be a non-negative number"
+        )),
+        Err(_) => Ok(DEFAULT_POOL_SIZE),
+    }
+}
+
+/// Returns the number of times that a failed connection attempt is retried, which is read from the `PSQL_CONNECT_RETRIES`
+/// environment variable. By default, failed connection attempts are not retried
+fn get_connect_retries() -> Result<u32> {
+    match std::env::var(ENV_PSQL_CONNECT_RETRIES) {
+        Ok(retries) => retries.trim().parse().context(format!(
+            "Value of {ENV_PSQL_CONNECT_RETRIES} must be a non-negative number"
+        )),
+        Err(_) => Ok(0),
+    }
+}
+
+/// Returns the delay before the first retry of a failed connection attempt, which is read from the
+/// `PSQL_CONNECT_BACKOFF_MS` environment variable
+fn get_connect_backoff() -> Result<Duration> {
+    match std::env::var(ENV_PSQL_CONNECT_BACKOFF_MS) {
+        Ok(backoff) => backoff
+            .trim()
+            .parse()
+            .map(Duration::from_millis)
+            .context(format!(
+            "Value of {ENV_PSQL_CONNECT_BACKOFF_MS} must be a non-negative number of milliseconds"
+        )),
+        Err(_) => Ok(DEFAULT_CONNECT_BACKOFF),
+    }
+}
+
+/// Returns whether a failed connection attempt might succeed if it is retried. This is the case if the database was
+/// not reachable at all (e.g. the connection was refused or timed out), or if the database is still starting up. Errors
+/// reported by a running database, such as invalid credentials, are not transient
+fn is_transient_connection_error(error: &postgres::Error) -> bool {
+    match error.as_db_error() {
+        Some(db_error) => *db_error.code() == SqlState::CANNOT_CONNECT_NOW,
+        None => error
+            .source()
+            .is_some_and(|source| source.is::<std::io::Error>()),
+    }
+}
+
+/// Calls `connect` until it succeeds, but at most `retries + 1` times. Only transient errors (see
+/// `is_transient_connection_error`) are retried. The delay between attempts starts at `backoff` and doubles after every
+/// attempt
+fn connect_with_retries<T>(
+    retries: u32,
+    backoff: Duration,
+    mut connect: impl FnMut() -> Result<T, postgres::Error>,
+) -> Result<T, postgres::Error> {
+    let mut attempt = 0;
+    loop {
+        match connect() {
+            Ok(client) => return Ok(client),
+            Err(why) if attempt < retries && is_transient_connection_error(&why) => {
+                let delay = backoff.saturating_mul(2u32.saturating_pow(attempt));
+                attempt += 1;
+                warn!("Connecting to the database failed ({why}), retrying in {delay:?} (retry {attempt} of {retries})");
+                std::thread::sleep(delay);
+            }
+            Err(why) => return Err(why),
+        }
+    }
+}
+
+/// Idle connections that can be reused by `connect`. All connections were created with the same configuration,
+/// identified by `config_key`. If the configuration changes, the idle connections are closed
+struct ConnectionPool {
+    config_key: String,
+    idle_connections: Vec<Client>,
+}
+
+static CONNECTION_POOL: Mutex<ConnectionPool> = Mutex::new(ConnectionPool {
+    config_key: String::new(),
+    idle_connections: Vec::new(),
+});
+
+/// A connection to the database that is returned to the connection pool when it is dropped, unless the connection was
+/// closed or the pool is full. Dereferences to a `Client`
+pub(crate) struct PooledClient {
+    client: Option<Client>,
+    config_key: String,
+    pool_size: usize,
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        self.client
+            .as_ref()
+            .expect("Client was already returned to the pool")
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.client
+            .as_mut()
+            .expect("Client was already returned to the pool")
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        let Some(client) = self.client.take() else {
+            return;
+        };
+        if client.is_closed() {
+            return;
+        }
+        let mut pool = ignore_poison(CONNECTION_POOL.lock());
+        if pool.config_key == self.config_key && pool.idle_connections.len() < self.pool_size {
+            pool.idle_connections.push(client);
+        }
+    }
+}
+
+/// Connects to the postgres DB and returns a Client. Idle connections from previous calls are reused if the connection
+/// configuration did not change in the meantime, otherwise a new connection is established. If the database is not
+/// reachable, connecting is retried as configured through `PSQL_CONNECT_RETRIES` and `PSQL_CONNECT_BACKOFF_MS`
+pub(crate) fn connect() -> Result<PooledClient> {
+    let connect_with_env_config = || -> Result<PooledClient> {
+        let config =
+            get_postgres_config().context("Can't get connection configuration for postgres DB")?;
+        let pool_size = get_pool_size()?;
+        // The debug output of the config does not contain the password, so add it explicitly
+        let config_key = format!("{config:?} {:?}", config.get_password());
+
+        loop {
+            let idle_client = {
+                let mut pool = ignore_poison(CONNECTION_POOL.lock());
+                if pool.config_key != config_key {
+                    pool.idle_connections.clear();
+                    pool.config_key = config_key.clone();
+                }
+                pool.idle_connections.pop()
+            };
+            let Some(mut client) = idle_client else {
+                break;
+            };
+            // The connection might have been closed by the server while it was idle
+            if client.is_valid(IDLE_CONNECTION_CHECK_TIMEOUT).is_ok() {
+                return Ok(PooledClient {
+                    client: Some(client),
+                    config_key,
+                    pool_size,
+                });
+            }
+        }
+
+        let retries = get_connect_retries()?;
+        let backoff = get_connect_backoff()?;
+        let client = if config.get_ssl_mode() == SslMode::Disable {
+            connect_with_retries(retries, backoff, || config.connect(NoTls))
+        } else {
+            let tls_connector = make_tls_connector()?;
+            connect_with_retries(retries, backoff, || config.connect(tls_connector.clone()))
+        }
+        .context(format!(
+            "Could not connect to postgres DB with config {:?}",
+            config
+        ))?;
+        Ok(PooledClient {
+            client: Some(client),
+            config_key,
+            pool_size,
+        })
+    };
+    connect_with_env_config().map_err(|why| ConnectionError(why).into())
+}
+
+/// Returns whether dry-run mode is enabled by setting the `PSQL_DRY_RUN` environment variable to `1`, `true` or `yes`. In
+/// dry-run mode, all statements are executed as usual (so constraint violations are still reported), but transactions
+/// that modify the database are rolled back instead of committed. Reading from the database is not affected
+pub fn is_dry_run() -> bool {
+    std::env::var(ENV_PSQL_DRY_RUN)
+        .map(|dry_run| {
+            matches!(
+                dry_run.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes"
+            )
+        })
+        .unwrap_or(false)
+}
+
+/// Commits `transaction`, or rolls it back in dry-run mode (see `is_dry_run`). All transactions that modify the database
+/// must be finished through this function instead of `Transaction::commit`
+pub(crate) fn commit(transaction: Transaction<'_>) -> Result<(), postgres::Error> {
+    if is_dry_run() {
+        info!("Dry run: rolling back transaction instead of committing it");
+        transaction.rollback()
+    } else {
+        transaction.commit()
+    }
+}
+
+/// Executes `sql`, a statement that modifies the database, with the given `params` and returns the number of modified
+/// rows. All statements that modify the database must be executed through this function (or `batch_execute`), which
+/// logs them at debug level in dry-run mode (see `is_dry_run`)
+pub(crate) fn execute<C: GenericClient>(
+    client: &mut C,
+    sql: &str,
+    params: &[&(dyn ToSql + Sync)],
+) -> Result<u64, postgres::Error> {
+    if is_dry_run() {
+        debug!("Dry run: {sql} {params:?}");
+    }
+    client.execute(sql, params)
+}
+
+/// Executes `sql`, which may contain multiple statements without parameters that modify the database. Just as
+/// `execute`, this logs the statements at debug level in dry-run mode
+pub(crate) fn batch_execute<C: GenericClient>(
+    client: &mut C,
+    sql: &str,
+) -> Result<(), postgres::Error> {
+    if is_dry_run() {
+        debug!("Dry run: {sql}");
+    }
+    client.batch_execute(sql)
+}
+
+/// Checks whether a connection to the database can be established with the current connection configuration, and
+/// whether the database responds to queries
+pub fn ping() -> Result<()> {
+    let mut client = connect()?;
+    client.simple_query("SELECT 1;").map_err(|why| {
+        ConnectionError(anyhow::Error::from(why).context("Database did not respond to query"))
+    })?;
+    Ok(())
+}
+
+/// All columns of the database schema that this library requires, as (table, column) pairs
+const SCHEMA_COLUMNS: [(&str, &str); 23] = [
+    ("experiments", "id"),
+    ("experiments", "researcher"),
+    ("experiments", "name"),
+    ("experiments", "description"),
+    ("variables", "id"),
+    ("variables", "name"),
+    ("variables", "description"),
+    ("variables", "unit"),
+    ("experiment_variables", "experiment_id"),
+    ("experiment_variables", "variable_id"),
+    ("experiment_runs", "runnumber"),
+    ("experiment_runs", "experimentid"),
+    ("experiment_runs", "id"),
+    ("experiment_runs", "timestamp"),
+    ("experiment_runs", "label"),
+    ("measurements", "experimentid"),
+    ("measurements", "variableid"),
+    ("measurements", "runid"),
+    ("measurements", "value"),
+    ("measurements", "timestamp"),
+    ("measurements", "numeric_value"),
+    ("experiment_tags", "experiment_id"),
+    ("experiment_tags", "tag"),
+];
+
+/// Returns all columns of the required database schema (see `test_data/dbschema.sql`) that do not exist in the
+/// database, as `table.column` strings. Columns that were added in later versions of the schema can be added with the
+/// migration functions, e.g. `add_run_label_column`
+pub fn missing_schema_columns() -> Result<Vec<String>> {
+    let mut client = connect()?;
+    let existing_columns = client
+        .query(
+            "SELECT table_name::text, column_name::text FROM information_schema.columns WHERE table_schema = 'public'",
+            &[],
+        )
+        .context("Failed to query columns of database schema")?
+        .iter()
+        .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+        .collect::<HashSet<_>>();
+    Ok(SCHEMA_COLUMNS
+        .iter()
+        .filter(|(table, column)| {
+            !existing_columns.contains(&(table.to_string(), column.to_string()))
+        })
+        .map(|(table, column)| format!("{table}.{column}"))
+        .collect())
+}
+
+/// Returns `true` if the database contains all tables and columns that this library requires
+pub fn schema_exists() -> Result<bool> {
+    Ok(missing_schema_columns()?.is_empty())
+}
+
+/// Fails if `table` is not one of the tables of the database schema. Table names can't be bound as parameters, so
+/// statements that contain a table name that is not a literal must check the name with this function first
+pub(crate) fn ensure_schema_table(table: &str) -> Result<()> {
+    if !SCHEMA_COLUMNS
+        .iter()
+        .any(|(schema_table, _)| *schema_table == table)
+    {
+        bail!("{table} is not a table of the database schema");
+    }
+    Ok(())
+}
+
+/// All columns that store (references to) unique IDs, as (table, column) pairs
+const ID_COLUMNS: [(&str, &str); 10] = [
+    ("experiments", "id"),
+    ("variables", "id"),
+    ("experiment_runs", "id"),
+    ("experiment_runs", "experimentid"),
+    ("experiment_variables", "experiment_id"),
+    ("experiment_variables", "variable_id"),
+    ("experiment_tags", "experiment_id"),
+    ("measurements", "experimentid"),
+    ("measurements", "variableid"),
+    ("measurements", "runid"),
+];
+
+/// Migrates the database schema so that all ID columns can hold IDs with up to `max_id_length` characters. The default
+/// schema uses `varchar(16)`, which is enough for the default unique ID generator. Call this before installing a unique
+/// ID generator that produces longer IDs (e.g. UUIDs). Columns are never narrowed, so `max_id_length` must be at least 16
+pub fn widen_id_columns(max_id_length: usize) -> Result<()> {
+    if max_id_length < 16 {
+        bail!("ID columns can't be narrowed below 16 characters (requested {max_id_length})");
+    }
+
+    let mut client = connect().context("Failed to connect to DB")?;
+    let mut transaction = client
+        .transaction()
+        .context("Failed to begin transaction")?;
+    // `max_id_length` is a number, so formatting it into the statement is safe
+    for (table, column) in ID_COLUMNS {
+        batch_execute(
+            &mut transaction,
+            &format!(
+                "ALTER TABLE public.{table} ALTER COLUMN {column} TYPE character varying({max_id_length});"
+            ),
+        )
+        .with_context(|| format!("Failed to widen column {column} of table {table}"))?;
+    }
+    commit(transaction).context("Failed to commit transaction for widening ID columns")?;
+    Ok(())
+}
+
+/// Migrates the database schema of databases that were created before runs could have labels, by adding the `label`
+/// column to the `experiment_runs` table. Does nothing if the column already exists
+pub fn add_run_label_column() -> Result<()> {
+    let mut client = connect().context("Failed to connect to DB")?;
+    let mut transaction = client
+        .transaction()
+        .context("Failed to begin transaction")?;
+    let column_exists = transaction
+        .query_opt(
+            "SELECT 1 FROM information_schema.columns WHERE table_schema = 'public' AND table_name = 'experiment_runs' AND column_name = 'label'",
+            &[],
+        )
+        .context("Failed to query columns of table experiment_runs")?
+        .is_some();
+    if !column_exists {
+        batch_execute(
+            &mut transaction,
+            "ALTER TABLE public.experiment_runs ADD COLUMN label text;
+                ALTER TABLE ONLY public.experiment_runs ADD CONSTRAINT experiment_runs_experimentid_label_key UNIQUE (experimentid, label);",
+        )
+        .context("Failed to add label column to table experiment_runs")?;
+    }
+    commit(transaction).context("Failed to commit transaction for adding label column")?;
+    Ok(())
+}
+
+/// Migrates the database schema of databases that were created before experiments could have tags, by adding the
+/// `experiment_tags` table. Does nothing if the table already exists. The `experiment_id` column gets the same type as
+/// the `id` column of the `experiments` table, so this also works after `widen_id_columns`
+pub fn add_experiment_tags_table() -> Result<()> {
+    let mut client = connect().context("Failed to connect to DB")?;
+    let mut transaction = client
+        .transaction()
+        .context("Failed to begin transaction")?;
+    let id_type: String = transaction
+        .query_one(
+            "SELECT format_type(atttypid, atttypmod) FROM pg_attribute WHERE attrelid = 'public.experiments'::regclass AND attname = 'id'",
+            &[],
+        )
+        .context("Failed to query type of column id of table experiments")?
+        .get(0);
+    // `id_type` comes from the system catalog, so formatting it into the statement is safe
+    batch_execute(
+        &mut transaction,
+        &format!(
+            "CREATE TABLE IF NOT EXISTS public.experiment_tags (
+                experiment_id {id_type} NOT NULL REFERENCES public.experiments(id),
+                tag text NOT NULL,
+                PRIMARY KEY (experiment_id, tag)
+            );"
+        ),
+    )
+    .context("Failed to create table experiment_tags")?;
+    commit(transaction).context("Failed to commit transaction for adding table experiment_tags")?;
+    Ok(())
+}
+
+/// Migrates the database schema of databases that were created before numeric measurement values were stored as
+/// numbers, by adding the `numeric_value` column to the `measurements` table. The column is filled for all existing
+/// measurements whose value is a number. Does nothing if the column already exists
+pub fn add_numeric_value_column() -> Result<()> {
+    let mut client = connect().context("Failed to connect to DB")?;
+    let mut transaction = client
+        .transaction()
+        .context("Failed to begin transaction")?;
+    let column_exists = transaction
+        .query_opt(
+            "SELECT 1 FROM information_schema.columns WHERE table_schema = 'public' AND table_name = 'measurements' AND column_name = 'numeric_value'",
+            &[],
+        )
+        .context("Failed to query columns of table measurements")?
+        .is_some();
+    if !column_exists {
+        batch_execute(
+            &mut transaction,
+            "ALTER TABLE public.measurements ADD COLUMN numeric_value double precision;",
+        )
+        .context("Failed to add numeric_value column to table measurements")?;
+        // Casting in the database fails for values that look like numbers but are out of range (e.g. `1e999`), so the
+        // values are parsed just like the values of new measurements
+        let (values, numeric_values): (Vec<String>, Vec<f64>) = transaction
+            .query(
+                "SELECT DISTINCT value FROM public.measurements WHERE value ~ $1",
+                &[&NUMERIC_VALUE_PATTERN],
+            )
+            .context("Failed to query numeric values of table measurements")?
+            .into_iter()
+            .filter_map(|row| {
+                let value: String = row.get(0);
+                let numeric_value = parse_numeric_value(&value)?;
+                Some((value, numeric_value))
+            })
+            .unzip();
+        execute(
+            &mut transaction,
+            "UPDATE public.measurements SET numeric_value = parsed.numeric_value FROM unnest($1::text[], $2::double precision[]) AS parsed(value, numeric_value) WHERE measurements.value = parsed.value",
+            &[&values, &numeric_values],
+        )
+        .context("Failed to fill numeric_value column of table measurements")?;
+    }
+    commit(transaction).context("Failed to commit transaction for adding numeric_value column")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_schema_tables_are_accepted() {
+        assert!(ensure_schema_table("experiment_runs").is_ok());
+        assert!(ensure_schema_table("measurements").is_ok());
+        assert!(ensure_schema_table("measurements; DROP TABLE measurements").is_err());
+        assert!(ensure_schema_table("pg_user").is_err());
+    }
+
+    #[test]
+    fn parse_ssl_modes() {
+        assert_eq!(SslMode::Disable, parse_ssl_mode("disable").unwrap());
+        assert_eq!(SslMode::Prefer, parse_ssl_mode("prefer").unwrap());
+        assert_eq!(SslMode::Require, parse_ssl_mode(" Require ").unwrap());
+        assert!(parse_ssl_mode("verify-full").is_err());
+        assert!(parse_ssl_mode("").is_err());
+    }
+
+    #[test]
+    fn unreachable_database_is_retried() {
+        let mut config = Client::configure();
+        // Nothing listens on port 1, so connecting is refused immediately
         config
-    ))?;
-    Ok(client)
+            .host("127.0.0.1")
+            .port(1)
+            .user("postgres")
+            .dbname("postgres");
+
+        let mut attempts = 0;
+        let result = connect_with_retries(2, Duration::from_millis(1), || {
+            attempts += 1;
+            config.connect(NoTls)
+        });
+        assert!(result.is_err());
+        assert_eq!(3, attempts);
+
+        attempts = 0;
+        let result = connect_with_retries(0, Duration::from_millis(1), || {
+            attempts += 1;
+            config.connect(NoTls)
+        });
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+    }
 }
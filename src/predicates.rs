@@ -0,0 +1,197 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Result};
+use postgres::types::ToSql;
+
+/// Matches measurement values that can be compared as numbers. Postgres can cast all of these to `double precision`
+pub(crate) const NUMERIC_VALUE_PATTERN: &str =
+    r"^\s*[-+]?([0-9]+\.?[0-9]*|\.[0-9]+)([eE][-+]?[0-9]+)?\s*$";
+
+/// Comparison operator of a `MeasurementPredicate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonOperator {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl ComparisonOperator {
+    fn sql(&self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Ne => "<>",
+            Self::Lt => "<",
+            Self::Lte => "<=",
+            Self::Gt => ">",
+            Self::Gte => ">=",
+        }
+    }
+}
+
+impl Display for ComparisonOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Self::Eq => "=",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Lte => "<=",
+            Self::Gt => ">",
+            Self::Gte => ">=",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+/// A condition on the measured value of a variable, used to filter runs through `Experiment::runs_matching`. If
+/// `value` is a number, measurements are compared numerically and measurements that are not numbers never match.
+/// Otherwise, measurements are compared as strings
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeasurementPredicate {
+    pub variable_name: String,
+    pub op: ComparisonOperator,
+    pub value: String,
+}
+
+impl MeasurementPredicate {
+    pub fn new<N: Into<String>, V: ToString>(
+        variable_name: N,
+        op: ComparisonOperator,
+        value: V,
+    ) -> Self {
+        Self {
+            variable_name: variable_name.into(),
+            op,
+            value: value.to_string(),
+        }
+    }
+
+    /// Returns an SQL condition that is true for rows of the `measurements` table (with alias `alias`) whose value
+    /// satisfies this predicate, together with its parameter. The parameter is referenced as `$value_param`. Numbers
+    /// are compared through the `numeric_value` column, so they never have to be parsed from the `value` column
+    pub(crate) fn sql_condition(
+        &self,
+        alias: &str,
+        value_param: usize,
+    ) -> (String, Box<dyn ToSql + Sync>) {
+        let op = self.op.sql();
+        match parse_numeric_value(&self.value) {
+            Some(number) => (
+                format!("{alias}.numeric_value {op} ${value_param}"),
+                Box::new(number),
+            ),
+            None => (
+                format!("{alias}.value {op} ${value_param}"),
+                Box::new(self.value.clone()),
+            ),
+        }
+    }
+}
+
+/// Parses a measurement value as a number, which is stored in the `numeric_value` column of the `measurements` table.
+/// Returns `None` for non-numeric and non-finite values
+pub(crate) fn parse_numeric_value(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+impl Display for MeasurementPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.variable_name, self.op, self.value)
+    }
+}
+
+impl FromStr for MeasurementPredicate {
+    type Err = anyhow::Error;
+
+    /// Parses expressions of the form `NAME OP VALUE`, e.g. `Runtime>500` or `Dataset = ds1`, where `OP` is one of `=`,
+    /// `==`, `!=`, `<`, `<=`, `>` or `>=`. Whitespace around the name and value is ignored
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(op_start) = s.find(['=', '!', '<', '>']) else {
+            bail!("Expected an expression of the form NAME OP VALUE with OP one of =, !=, <, <=, >, >=, but got \"{s}\"");
+        };
+        let (variable_name, rest) = s.split_at(op_start);
+        // Longer operators come first so that e.g. '>=' is not parsed as '>' followed by the value '=...'
+        let (op, value) = [
+            ("==", ComparisonOperator::Eq),
+            ("!=", ComparisonOperator::Ne),
+            ("<=", ComparisonOperator::Lte),
+            (">=", ComparisonOperator::Gte),
+            ("=", ComparisonOperator::Eq),
+            ("<", ComparisonOperator::Lt),
+            (">", ComparisonOperator::Gt),
+        ]
+        .into_iter()
+        .find_map(|(symbol, op)| rest.strip_prefix(symbol).map(|value| (op, value)))
+        .ok_or_else(|| anyhow::anyhow!("Invalid comparison operator in \"{s}\""))?;
+
+        let variable_name = variable_name.trim();
+        if variable_name.is_empty() {
+            bail!("Missing variable name in \"{s}\"");
+        }
+        Ok(Self::new(variable_name, op, value.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_predicates() {
+        assert_eq!(
+            MeasurementPredicate::new("Runtime", ComparisonOperator::Gt, "500"),
+            "Runtime>500".parse().unwrap()
+        );
+        assert_eq!(
+            MeasurementPredicate::new("Runtime", ComparisonOperator::Gte, "500"),
+            " Runtime >= 500 ".parse().unwrap()
+        );
+        assert_eq!(
+            MeasurementPredicate::new("Data set", ComparisonOperator::Eq, "ds 1"),
+            "Data set == ds 1".parse().unwrap()
+        );
+        assert_eq!(
+            MeasurementPredicate::new("Host", ComparisonOperator::Eq, ""),
+            "Host=".parse().unwrap()
+        );
+        assert_eq!(
+            MeasurementPredicate::new("Host", ComparisonOperator::Ne, "node1"),
+            "Host!=node1".parse().unwrap()
+        );
+        assert_eq!(
+            MeasurementPredicate::new("x", ComparisonOperator::Lte, "-1.5"),
+            "x<=-1.5".parse().unwrap()
+        );
+        // Only the first operator is part of the expression, the rest belongs to the value
+        assert_eq!(
+            MeasurementPredicate::new("x", ComparisonOperator::Eq, "a=b"),
+            "x=a=b".parse().unwrap()
+        );
+
+        assert!("Runtime".parse::<MeasurementPredicate>().is_err());
+        assert!(">500".parse::<MeasurementPredicate>().is_err());
+        assert!("Runtime!500".parse::<MeasurementPredicate>().is_err());
+    }
+
+    #[test]
+    fn numeric_predicates_compare_numbers() {
+        let (condition, _) =
+            MeasurementPredicate::new("x", ComparisonOperator::Gt, "500").sql_condition("m", 3);
+        assert_eq!("m.numeric_value > $3", condition);
+
+        let (condition, _) =
+            MeasurementPredicate::new("x", ComparisonOperator::Eq, "abc").sql_condition("m", 3);
+        assert_eq!("m.value = $3", condition);
+
+        // Non-finite numbers such as NaN are never stored as numeric values, so they are compared as strings
+        let (condition, _) =
+            MeasurementPredicate::new("x", ComparisonOperator::Eq, "NaN").sql_condition("m", 3);
+        assert_eq!("m.value = $3", condition);
+    }
+}
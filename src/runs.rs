@@ -1,8 +1,12 @@
-use std::time::SystemTime;
+use std::{collections::VecDeque, time::SystemTime};
 
-use crate::{Experiment, Measurement};
+use crate::{connect, execute, Experiment, Measurement, MeasurementPredicate, PooledClient};
 use anyhow::{bail, Context, Result};
-use postgres::{GenericClient, Row};
+use log::warn;
+use postgres::{types::ToSql, GenericClient, Row};
+
+/// Number of rows that a `RunStream` fetches from the database at once
+const RUN_STREAM_FETCH_SIZE: usize = 1000;
 
 /// Raw structure for an experiment run, matching the scheme of the 'experiment_runs' table. This does not know anything
 /// about the measurements associated with the run, for that use the `Run` structure
@@ -12,6 +16,7 @@ pub struct RawRun {
     pub run_number: usize,
     pub experiment_id: String,
     pub timestamp: SystemTime,
+    pub label: Option<String>,
 }
 
 impl RawRun {
@@ -63,14 +68,137 @@ impl RawRun {
         }
     }
 
+    /// Try to fetch the run with the given label (see `RunContext::set_label`) of the given experiment
+    pub fn from_label_and_experiment<C: GenericClient>(
+        label: &str,
+        experiment: &Experiment,
+        client: &mut C,
+    ) -> Result<Option<Self>> {
+        let row = client
+            .query_opt(
+                "SELECT * FROM experiment_runs WHERE label = $1 AND experimentid = $2",
+                &[&label, &experiment.id()],
+            )
+            .context("Failed to execute query")?;
+        row.map(|row| {
+            (&row)
+                .try_into()
+                .context("Failed to convert DB response to RawRun structure")
+        })
+        .transpose()
+    }
+
+    /// Fetches all runs of the given experiment from the database, sorted by run number
+    pub fn all_of_experiment<C: GenericClient>(
+        experiment: &Experiment,
+        client: &mut C,
+    ) -> Result<Vec<Self>> {
+        let rows = client
+            .query(
+                "SELECT * FROM experiment_runs WHERE experimentid = $1 ORDER BY runnumber",
+                &[&experiment.id()],
+            )
+            .context("Failed to execute query")?;
+        rows.iter()
+            .map(|row| {
+                row.try_into()
+                    .context("Failed to convert DB response to RawRun structure")
+            })
+            .collect()
+    }
+
+    /// Fetches a page of the runs of the given experiment from the database, sorted by run number. Skips the first
+    /// `offset` runs and returns at most `limit` runs, or all remaining runs if `limit` is `None`
+    pub fn page_of_experiment<C: GenericClient>(
+        experiment: &Experiment,
+        limit: Option<usize>,
+        offset: usize,
+        client: &mut C,
+    ) -> Result<Vec<Self>> {
+        let limit = limit.map(|limit| limit as i64);
+        let rows = client
+            .query(
+                "SELECT * FROM experiment_runs WHERE experimentid = $1 ORDER BY runnumber, id LIMIT $2 OFFSET $3",
+                &[&experiment.id(), &limit, &(offset as i64)],
+            )
+            .context("Failed to execute query")?;
+        rows.iter()
+            .map(|row| {
+                row.try_into()
+                    .context("Failed to convert DB response to RawRun structure")
+            })
+            .collect()
+    }
+
+    /// Fetches the most recent run of the given experiment from the database, i.e. the run with the latest timestamp. Runs
+    /// with the same timestamp are ordered by run number. Returns `None` if the experiment has no runs
+    pub fn latest_of_experiment<C: GenericClient>(
+        experiment: &Experiment,
+        client: &mut C,
+    ) -> Result<Option<Self>> {
+        let row = client
+            .query_opt(
+                "SELECT * FROM experiment_runs WHERE experimentid = $1 ORDER BY timestamp DESC, runnumber DESC LIMIT 1",
+                &[&experiment.id()],
+            )
+            .context("Failed to execute query")?;
+        row.map(|row| {
+            (&row)
+                .try_into()
+                .context("Failed to convert DB response to RawRun structure")
+        })
+        .transpose()
+    }
+
+    /// Fetches all runs of the given experiment that have a measurement satisfying each of the `predicates`, sorted by run
+    /// number
+    pub fn matching_of_experiment<C: GenericClient>(
+        experiment: &Experiment,
+        predicates: &[MeasurementPredicate],
+        client: &mut C,
+    ) -> Result<Vec<Self>> {
+        let mut query = String::from("SELECT * FROM experiment_runs WHERE experimentid = $1");
+        let mut params: Vec<Box<dyn ToSql + Sync>> = vec![Box::new(experiment.id().to_owned())];
+        for predicate in predicates {
+            let name_param = params.len() + 1;
+            let (condition, value) = predicate.sql_condition("m", name_param + 1);
+            query += &format!(
+                " AND EXISTS (SELECT 1 FROM measurements m JOIN variables v ON m.variableid = v.id WHERE m.runid = experiment_runs.id AND v.name = ${name_param} AND {condition})"
+            );
+            params.push(Box::new(predicate.variable_name.clone()));
+            params.push(value);
+        }
+        query += " ORDER BY runnumber, id";
+
+        let params = params
+            .iter()
+            .map(|param| param.as_ref() as &(dyn ToSql + Sync))
+            .collect::<Vec<_>>();
+        let rows = client
+            .query(&query, &params)
+            .context("Failed to execute query")?;
+        rows.iter()
+            .map(|row| {
+                row.try_into()
+                    .context("Failed to convert DB response to RawRun structure")
+            })
+            .collect()
+    }
+
     /// Deletes this run and all associated measurements from the database
     pub(crate) fn delete_from_database<C: GenericClient>(self, client: &mut C) -> Result<()> {
-        client
-            .execute("DELETE FROM measurements WHERE runid = $1", &[&self.run_id])
-            .with_context(|| format!("Failed to delete measurements for run {}", self.run_id))?;
-        client
-            .execute("DELETE FROM experiment_runs WHERE id = $1", &[&self.run_id])
-            .with_context(|| format!("Failed to delete experiment run {}", self.run_id))?;
+        execute(
+            client,
+            "DELETE FROM measurements WHERE runid = $1",
+            &[&self.run_id],
+        )
+        .with_context(|| format!("Failed to delete measurements for run {}", self.run_id))?;
+        execute(
+            client,
+            "DELETE FROM experiment_runs WHERE id = $1",
+            &[&self.run_id],
+        )
+        .with_context(|| format!("Failed to delete experiment run {}", self.run_id))?;
         Ok(())
     }
 }
@@ -89,11 +217,15 @@ impl TryFrom<&'_ Row> for RawRun {
         let timestamp = value
             .try_get("timestamp")
             .context("timestamp field not found in row")?;
+        let label = value
+            .try_get("label")
+            .context("label field not found in row")?;
         Ok(Self {
             experiment_id,
             run_id,
             run_number: run_number as usize,
             timestamp,
+            label,
         })
     }
 }
@@ -102,14 +234,27 @@ impl TryFrom<&'_ Row> for RawRun {
 pub struct Run<'a> {
     run_id: String,
     run_number: usize,
+    label: Option<String>,
+    timestamp: SystemTime,
     measurements: Vec<Measurement<'a>>,
 }
 
 impl<'a> Run<'a> {
-    pub fn new(run_id: String, run_number: usize, measurements: Vec<Measurement<'a>>) -> Self {
+    /// Creates a new run from the given measurements. The measurements are sorted by variable name, so that the
+    /// order of measurements does not depend on the order in which the database returned them
+    pub fn new(
+        run_id: String,
+        run_number: usize,
+        label: Option<String>,
+        timestamp: SystemTime,
+        mut measurements: Vec<Measurement<'a>>,
+    ) -> Self {
+        sort_measurements_by_variable_name(&mut measurements);
         Self {
             run_id,
             run_number,
+            label,
+            timestamp,
             measurements,
         }
     }
@@ -118,11 +263,13 @@ impl<'a> Run<'a> {
         let measurements = experiment
             .measurements_for_run(&raw_run.run_id)
             .context("Failed to fetch measurements from DB")?;
-        Ok(Self {
+        Ok(Self::new(
+            raw_run.run_id.to_owned(),
+            raw_run.run_number,
+            raw_run.label.clone(),
+            raw_run.timestamp,
             measurements,
-            run_id: raw_run.run_id.to_owned(),
-            run_number: raw_run.run_number,
-        })
+        ))
     }
 
     pub fn id(&self) -> &str {
@@ -133,7 +280,197 @@ impl<'a> Run<'a> {
         self.run_number
     }
 
+    /// The human-readable label of this run, if it has one
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// The point in time at which this run was recorded
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// The measurements of this run, sorted by variable name
     pub fn measurements(&self) -> &[Measurement<'a>] {
         &self.measurements
     }
 }
+
+/// Sorts the given measurements by the names of their variables
+pub(crate) fn sort_measurements_by_variable_name(measurements: &mut [Measurement<'_>]) {
+    measurements.sort_by(|a, b| {
+        a.variable()
+            .template()
+            .name()
+            .cmp(b.variable().template().name())
+    });
+}
+
+/// A run whose measurements are still being collected by a `RunStream`
+struct PartialRun<'a> {
+    run_id: String,
+    run_number: usize,
+    label: Option<String>,
+    timestamp: SystemTime,
+    measurements: Vec<Measurement<'a>>,
+}
+
+impl<'a> PartialRun<'a> {
+    fn into_run(self) -> Run<'a> {
+        Run::new(
+            self.run_id,
+            self.run_number,
+            self.label,
+            self.timestamp,
+            self.measurements,
+        )
+    }
+}
+
+/// Iterator over all runs of an experiment, sorted by run number, which fetches the measurements from the database in
+/// chunks instead of loading them all at once. Created through `Experiment::stream_runs`. The stream holds its own
+/// database connection with an open transaction until it is exhausted or dropped
+pub struct RunStream<'a> {
+    experiment: &'a Experiment,
+    client: PooledClient,
+    buffered_rows: VecDeque<Row>,
+    current_run: Option<PartialRun<'a>>,
+    in_transaction: bool,
+    exhausted: bool,
+    runs_returned: usize,
+    on_progress: Option<Box<dyn FnMut(usize) + 'a>>,
+}
+
+impl<'a> RunStream<'a> {
+    pub(crate) fn new(experiment: &'a Experiment) -> Result<Self> {
+        let client = connect().context("Failed to connect to DB")?;
+        let mut stream = Self {
+            experiment,
+            client,
+            buffered_rows: Default::default(),
+            current_run: None,
+            in_transaction: false,
+            exhausted: false,
+            runs_returned: 0,
+            on_progress: None,
+        };
+        // Cursors only live within a transaction. Since the stream owns its connection, a plain BEGIN is used instead
+        // of a `Transaction`, which would borrow the connection
+        stream
+            .client
+            .batch_execute("BEGIN")
+            .context("Can't start transaction")?;
+        stream.in_transaction = true;
+        stream
+            .client
+            .execute(
+                "DECLARE run_stream NO SCROLL CURSOR FOR SELECT experiment_runs.id, value, measurements.timestamp, experiment_runs.timestamp AS run_timestamp, runnumber, label, variableid FROM measurements INNER JOIN experiment_runs ON measurements.runid = experiment_runs.id WHERE experiment_runs.experimentid = $1 ORDER BY runnumber, experiment_runs.id",
+                &[&experiment.id()],
+            )
+            .context("Failed to declare cursor for runs")?;
+        Ok(stream)
+    }
+
+    /// Sets a callback that reports the progress of this stream, e.g. to show a progress indicator for experiments with
+    /// many runs. Whenever the stream fetches the next chunk of measurements from the database, `on_progress` is called
+    /// with the number of runs that the stream has returned so far
+    pub fn with_progress<F: FnMut(usize) + 'a>(mut self, on_progress: F) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    fn fetch_rows(&mut self) -> Result<()> {
+        if let Some(on_progress) = &mut self.on_progress {
+            on_progress(self.runs_returned);
+        }
+        let rows = self
+            .client
+            .query(
+                &format!("FETCH {RUN_STREAM_FETCH_SIZE} FROM run_stream"),
+                &[],
+            )
+            .context("Failed to fetch rows from cursor")?;
+        if rows.len() < RUN_STREAM_FETCH_SIZE {
+            self.exhausted = true;
+            self.client
+                .batch_execute("CLOSE run_stream; COMMIT")
+                .context("Failed to close cursor")?;
+            self.in_transaction = false;
+        }
+        self.buffered_rows.extend(rows);
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for RunStream<'a> {
+    type Item = Result<Run<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.buffered_rows.is_empty() && !self.exhausted {
+                if let Err(why) = self.fetch_rows() {
+                    self.exhausted = true;
+                    self.current_run = None;
+                    return Some(Err(why));
+                }
+            }
+            let Some(row) = self.buffered_rows.pop_front() else {
+                let last_run = self.current_run.take()?;
+                self.runs_returned += 1;
+                return Some(Ok(last_run.into_run()));
+            };
+
+            let run_id: String = row.get("id");
+            let variable_id: &str = row.get("variableid");
+            let value: Option<String> = row.get("value");
+            let timestamp: SystemTime = row.get("timestamp");
+            let run_number: i32 = row.get("runnumber");
+            let label: Option<String> = row.get("label");
+            let run_timestamp: SystemTime = row.get("run_timestamp");
+
+            // Same as in `Experiment::all_runs`, measurements of variables that are not part of the experiment anymore
+            // are skipped
+            let Some(variable) = self
+                .experiment
+                .variables()
+                .find(|variable| variable.id() == variable_id)
+            else {
+                warn!("Skipping measurement of run {run_id} for variable {variable_id} which is not a variable of experiment {}", self.experiment.name());
+                continue;
+            };
+            let measurement = Measurement::new(variable, value, timestamp, run_number);
+
+            // Rows are sorted by run, so a new run ID means that all measurements of the current run were seen
+            match &mut self.current_run {
+                Some(current_run) if current_run.run_id == run_id => {
+                    current_run.measurements.push(measurement);
+                }
+                _ => {
+                    let finished_run = self.current_run.replace(PartialRun {
+                        run_id,
+                        run_number: run_number as usize,
+                        label,
+                        timestamp: run_timestamp,
+                        measurements: vec![measurement],
+                    });
+                    if let Some(finished_run) = finished_run {
+                        self.runs_returned += 1;
+                        return Some(Ok(finished_run.into_run()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RunStream<'_> {
+    fn drop(&mut self) {
+        // Don't return a connection with an open transaction to the connection pool. If the rollback fails, the
+        // connection is unusable and will be discarded by the pool
+        if self.in_transaction {
+            if let Err(why) = self.client.batch_execute("ROLLBACK") {
+                warn!("Failed to roll back transaction of run stream: {why}");
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+
+use crate::connect;
+
+/// Number of entries of each kind in the whole database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalStats {
+    pub experiments: usize,
+    pub variables: usize,
+    pub runs: usize,
+    pub measurements: usize,
+}
+
+impl GlobalStats {
+    /// Fetches the number of experiments, variables, runs, and measurements in the database. This only runs `COUNT(*)`
+    /// queries, so it is cheap even for large databases
+    pub fn fetch() -> Result<Self> {
+        let mut client = connect().context("Failed to connect to DB")?;
+        let row = client
+            .query_one(
+                "SELECT
+                (SELECT COUNT(*) FROM experiments) AS experiments,
+                (SELECT COUNT(*) FROM variables) AS variables,
+                (SELECT COUNT(*) FROM experiment_runs) AS runs,
+                (SELECT COUNT(*) FROM measurements) AS measurements;",
+                &[],
+            )
+            .context("Failed to execute query")?;
+        let get_count = |column: &str| -> Result<usize> {
+            let count: i64 = row
+                .try_get(column)
+                .with_context(|| format!("{column} field not found in row"))?;
+            Ok(count as usize)
+        };
+        Ok(Self {
+            experiments: get_count("experiments")?,
+            variables: get_count("variables")?,
+            runs: get_count("runs")?,
+            measurements: get_count("measurements")?,
+        })
+    }
+}
@@ -0,0 +1,390 @@
+use std::{borrow::Cow, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+
+/// Canonical symbol for nanoseconds
+pub const UNIT_NANOSECONDS: &str = "ns";
+/// Canonical symbol for microseconds
+pub const UNIT_MICROSECONDS: &str = "us";
+/// Canonical symbol for milliseconds
+pub const UNIT_MILLISECONDS: &str = "ms";
+/// Canonical symbol for seconds
+pub const UNIT_SECONDS: &str = "s";
+/// Canonical symbol for minutes
+pub const UNIT_MINUTES: &str = "min";
+/// Canonical symbol for hours
+pub const UNIT_HOURS: &str = "h";
+/// Canonical symbol for bytes
+pub const UNIT_BYTES: &str = "B";
+/// Canonical symbol for kibibytes (1024 bytes)
+pub const UNIT_KIBIBYTES: &str = "KiB";
+/// Canonical symbol for mebibytes (1024^2 bytes)
+pub const UNIT_MEBIBYTES: &str = "MiB";
+/// Canonical symbol for gibibytes (1024^3 bytes)
+pub const UNIT_GIBIBYTES: &str = "GiB";
+/// Canonical symbol for kilobytes (1000 bytes)
+pub const UNIT_KILOBYTES: &str = "kB";
+/// Canonical symbol for megabytes (1000^2 bytes)
+pub const UNIT_MEGABYTES: &str = "MB";
+/// Canonical symbol for gigabytes (1000^3 bytes)
+pub const UNIT_GIGABYTES: &str = "GB";
+/// Canonical symbol for bytes per second
+pub const UNIT_BYTES_PER_SECOND: &str = "B/s";
+/// Canonical symbol for mebibytes per second
+pub const UNIT_MEBIBYTES_PER_SECOND: &str = "MiB/s";
+/// Canonical symbol for gibibytes per second
+pub const UNIT_GIBIBYTES_PER_SECOND: &str = "GiB/s";
+/// Canonical symbol for megabytes per second
+pub const UNIT_MEGABYTES_PER_SECOND: &str = "MB/s";
+/// Canonical symbol for gigabytes per second
+pub const UNIT_GIGABYTES_PER_SECOND: &str = "GB/s";
+
+/// The physical quantity that a unit measures. Only values of units with the same quantity can be converted into each
+/// other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quantity {
+    Time,
+    Data,
+    DataRate,
+}
+
+impl Quantity {
+    /// The unit that all values of this quantity are converted to by `to_base_unit`
+    fn base_unit(&self) -> &'static str {
+        match self {
+            Self::Time => UNIT_SECONDS,
+            Self::Data => UNIT_BYTES,
+            Self::DataRate => UNIT_BYTES_PER_SECOND,
+        }
+    }
+}
+
+/// A well-known unit together with the spellings that are recognized as synonyms for it
+struct KnownUnit {
+    symbol: &'static str,
+    quantity: Quantity,
+    /// Size of the unit relative to the smallest known unit of the same quantity (nanoseconds, bytes or bytes per
+    /// second). All factors are integers, so that conversions between units are as exact as possible
+    factor: f64,
+    /// Alternative symbols. These are case-sensitive, since e.g. 'MB' (megabytes) and 'mb' (millibits) differ
+    aliases: &'static [&'static str],
+    /// Spelled-out names of the unit. These are matched case-insensitively
+    names: &'static [&'static str],
+}
+
+const KNOWN_UNITS: &[KnownUnit] = &[
+    KnownUnit {
+        symbol: UNIT_NANOSECONDS,
+        quantity: Quantity::Time,
+        factor: 1.0,
+        aliases: &["nsec", "nsecs"],
+        names: &["nanosecond", "nanoseconds"],
+    },
+    KnownUnit {
+        symbol: UNIT_MICROSECONDS,
+        quantity: Quantity::Time,
+        factor: 1e3,
+        aliases: &["µs", "μs", "usec", "usecs"],
+        names: &["microsecond", "microseconds"],
+    },
+    KnownUnit {
+        symbol: UNIT_MILLISECONDS,
+        quantity: Quantity::Time,
+        factor: 1e6,
+        aliases: &["msec", "msecs"],
+        names: &["millisecond", "milliseconds"],
+    },
+    KnownUnit {
+        symbol: UNIT_SECONDS,
+        quantity: Quantity::Time,
+        factor: 1e9,
+        aliases: &["sec", "secs"],
+        names: &["second", "seconds"],
+    },
+    KnownUnit {
+        symbol: UNIT_MINUTES,
+        quantity: Quantity::Time,
+        factor: 60e9,
+        aliases: &["mins"],
+        names: &["minute", "minutes"],
+    },
+    KnownUnit {
+        symbol: UNIT_HOURS,
+        quantity: Quantity::Time,
+        factor: 3600e9,
+        aliases: &["hr", "hrs"],
+        names: &["hour", "hours"],
+    },
+    KnownUnit {
+        symbol: UNIT_BYTES,
+        quantity: Quantity::Data,
+        factor: 1.0,
+        aliases: &[],
+        names: &["byte", "bytes"],
+    },
+    KnownUnit {
+        symbol: UNIT_KIBIBYTES,
+        quantity: Quantity::Data,
+        factor: 1024.0,
+        aliases: &[],
+        names: &["kibibyte", "kibibytes"],
+    },
+    KnownUnit {
+        symbol: UNIT_MEBIBYTES,
+        quantity: Quantity::Data,
+        factor: 1048576.0,
+        aliases: &[],
+        names: &["mebibyte", "mebibytes"],
+    },
+    KnownUnit {
+        symbol: UNIT_GIBIBYTES,
+        quantity: Quantity::Data,
+        factor: 1073741824.0,
+        aliases: &[],
+        names: &["gibibyte", "gibibytes"],
+    },
+    KnownUnit {
+        symbol: UNIT_KILOBYTES,
+        quantity: Quantity::Data,
+        factor: 1e3,
+        aliases: &["KB"],
+        names: &["kilobyte", "kilobytes"],
+    },
+    KnownUnit {
+        symbol: UNIT_MEGABYTES,
+        quantity: Quantity::Data,
+        factor: 1e6,
+        aliases: &[],
+        names: &["megabyte", "megabytes"],
+    },
+    KnownUnit {
+        symbol: UNIT_GIGABYTES,
+        quantity: Quantity::Data,
+        factor: 1e9,
+        aliases: &[],
+        names: &["gigabyte", "gigabytes"],
+    },
+    KnownUnit {
+        symbol: UNIT_BYTES_PER_SECOND,
+        quantity: Quantity::DataRate,
+        factor: 1.0,
+        aliases: &["Bps"],
+        names: &["bytes/s", "bytes/sec", "bytes per second"],
+    },
+    KnownUnit {
+        symbol: UNIT_MEBIBYTES_PER_SECOND,
+        quantity: Quantity::DataRate,
+        factor: 1048576.0,
+        aliases: &[],
+        names: &["mebibytes/s", "mebibytes per second"],
+    },
+    KnownUnit {
+        symbol: UNIT_GIBIBYTES_PER_SECOND,
+        quantity: Quantity::DataRate,
+        factor: 1073741824.0,
+        aliases: &[],
+        names: &["gibibytes/s", "gibibytes per second"],
+    },
+    KnownUnit {
+        symbol: UNIT_MEGABYTES_PER_SECOND,
+        quantity: Quantity::DataRate,
+        factor: 1e6,
+        aliases: &["MBps"],
+        names: &["megabytes/s", "megabytes per second"],
+    },
+    KnownUnit {
+        symbol: UNIT_GIGABYTES_PER_SECOND,
+        quantity: Quantity::DataRate,
+        factor: 1e9,
+        aliases: &["GBps"],
+        names: &["gigabytes/s", "gigabytes per second"],
+    },
+];
+
+fn find_known_unit(unit: &str) -> Option<&'static KnownUnit> {
+    let unit = unit.trim();
+    KNOWN_UNITS.iter().find(|known_unit| {
+        known_unit.symbol == unit
+            || known_unit.aliases.contains(&unit)
+            || known_unit
+                .names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(unit))
+    })
+}
+
+/// Normalizes the given unit string. Common synonyms of well-known units (e.g. "msec" or "milliseconds") are mapped
+/// to their canonical symbol (e.g. "ms"). Units that are not known are returned as-is (minus surrounding whitespace),
+/// so free-form units are still allowed
+pub fn normalize_unit(unit: &str) -> Cow<'static, str> {
+    find_known_unit(unit)
+        .map(|known_unit| Cow::Borrowed(known_unit.symbol))
+        .unwrap_or_else(|| Cow::Owned(unit.trim().to_owned()))
+}
+
+/// Like `normalize_unit`, but borrows from `unit` instead of copying it if the unit is not known
+pub(crate) fn canonical_unit(unit: &str) -> &str {
+    find_known_unit(unit).map_or(unit.trim(), |known_unit| known_unit.symbol)
+}
+
+/// Converts the given duration into the given unit of time, which can be any of the time units (e.g. `UNIT_SECONDS`) or
+/// one of their synonyms. Returns `None` if `unit` is not a unit of time
+pub fn duration_in_unit(duration: Duration, unit: &str) -> Option<f64> {
+    let nanoseconds_per_unit = match normalize_unit(unit).as_ref() {
+        UNIT_NANOSECONDS => 1.0,
+        UNIT_MICROSECONDS => 1e3,
+        UNIT_MILLISECONDS => 1e6,
+        UNIT_SECONDS => 1e9,
+        UNIT_MINUTES => 60e9,
+        UNIT_HOURS => 3600e9,
+        _ => return None,
+    };
+    Some(duration.as_nanos() as f64 / nanoseconds_per_unit)
+}
+
+/// Converts `value` from the unit `from_unit` into the unit `to_unit`, e.g. 1500 "ms" into 1.5 "s". Both units can be
+/// any of the well-known units or their synonyms (see `normalize_unit`). Fails if either unit is unknown, or if the
+/// units measure different quantities (e.g. "ms" and "B")
+pub fn convert_unit(value: f64, from_unit: &str, to_unit: &str) -> Result<f64> {
+    let from = find_known_unit(from_unit)
+        .ok_or_else(|| anyhow!("Unknown unit \"{from_unit}\", can't convert its values"))?;
+    let to = find_known_unit(to_unit)
+        .ok_or_else(|| anyhow!("Unknown unit \"{to_unit}\", can't convert values into it"))?;
+    if from.quantity != to.quantity {
+        bail!(
+            "Can't convert values from unit \"{}\" into unit \"{}\" since they measure different quantities",
+            from.symbol,
+            to.symbol
+        );
+    }
+    if from.factor == to.factor {
+        return Ok(value);
+    }
+    Ok(value * from.factor / to.factor)
+}
+
+/// Returns the base unit of the quantity that `unit` measures, e.g. `UNIT_SECONDS` for all units of time and
+/// `UNIT_BYTES` for all units of data sizes. Returns `None` if `unit` is not a well-known unit
+pub fn base_unit(unit: &str) -> Option<&'static str> {
+    find_known_unit(unit).map(|known_unit| known_unit.quantity.base_unit())
+}
+
+/// Converts `value` from `unit` into the base unit of its quantity (see `base_unit`), so that values that were measured
+/// in different units can be compared and aggregated. Returns the converted value together with the base unit, or
+/// `None` if `unit` is not a well-known unit
+pub fn to_base_unit(value: f64, unit: &str) -> Option<(f64, &'static str)> {
+    let base_unit = base_unit(unit)?;
+    let value = convert_unit(value, unit, base_unit).ok()?;
+    Some((value, base_unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_durations() {
+        let duration = Duration::from_millis(1500);
+        assert_eq!(
+            Some(1_500_000_000.0),
+            duration_in_unit(duration, UNIT_NANOSECONDS)
+        );
+        assert_eq!(
+            Some(1_500_000.0),
+            duration_in_unit(duration, UNIT_MICROSECONDS)
+        );
+        assert_eq!(Some(1500.0), duration_in_unit(duration, UNIT_MILLISECONDS));
+        assert_eq!(Some(1.5), duration_in_unit(duration, UNIT_SECONDS));
+        assert_eq!(Some(1.5), duration_in_unit(duration, "seconds"));
+        assert_eq!(
+            Some(0.5),
+            duration_in_unit(Duration::from_secs(1800), UNIT_HOURS)
+        );
+        assert_eq!(
+            Some(2.0),
+            duration_in_unit(Duration::from_secs(120), UNIT_MINUTES)
+        );
+        assert_eq!(None, duration_in_unit(duration, UNIT_BYTES));
+        assert_eq!(None, duration_in_unit(duration, ""));
+    }
+
+    #[test]
+    fn convert_units() {
+        assert_eq!(
+            1.5,
+            convert_unit(1500.0, UNIT_MILLISECONDS, UNIT_SECONDS).unwrap()
+        );
+        assert_eq!(1500.0, convert_unit(1.5, "seconds", "msec").unwrap());
+        assert_eq!(90.0, convert_unit(1.5, UNIT_HOURS, UNIT_MINUTES).unwrap());
+        assert_eq!(
+            2048.0,
+            convert_unit(2.0, UNIT_KIBIBYTES, UNIT_BYTES).unwrap()
+        );
+        assert_eq!(
+            0.5,
+            convert_unit(512.0, UNIT_BYTES, UNIT_KIBIBYTES).unwrap()
+        );
+        assert_eq!(
+            1024.0,
+            convert_unit(1.0, UNIT_GIBIBYTES, UNIT_MEBIBYTES).unwrap()
+        );
+        assert_eq!(
+            1.024,
+            convert_unit(1.0, UNIT_KIBIBYTES, UNIT_KILOBYTES).unwrap()
+        );
+        assert_eq!(
+            1000.0,
+            convert_unit(1.0, UNIT_GIGABYTES_PER_SECOND, UNIT_MEGABYTES_PER_SECOND).unwrap()
+        );
+        assert_eq!(42.0, convert_unit(42.0, "ms", "milliseconds").unwrap());
+
+        // Incompatible or unknown units
+        assert!(convert_unit(1.0, UNIT_MILLISECONDS, UNIT_BYTES).is_err());
+        assert!(convert_unit(1.0, UNIT_BYTES, UNIT_BYTES_PER_SECOND).is_err());
+        assert!(convert_unit(1.0, "points", UNIT_SECONDS).is_err());
+        assert!(convert_unit(1.0, UNIT_SECONDS, "").is_err());
+    }
+
+    #[test]
+    fn convert_to_base_units() {
+        assert_eq!(Some((0.25, UNIT_SECONDS)), to_base_unit(250.0, "ms"));
+        assert_eq!(Some((3072.0, UNIT_BYTES)), to_base_unit(3.0, "KiB"));
+        assert_eq!(
+            Some((2e6, UNIT_BYTES_PER_SECOND)),
+            to_base_unit(2.0, UNIT_MEGABYTES_PER_SECOND)
+        );
+        assert_eq!(None, to_base_unit(1.0, "none"));
+        assert_eq!(Some(UNIT_SECONDS), base_unit("hours"));
+        assert_eq!(None, base_unit("points/s"));
+    }
+
+    #[test]
+    fn normalize_synonyms() {
+        for synonym in ["ms", "msec", "msecs", "millisecond", "Milliseconds", " ms "] {
+            assert_eq!(UNIT_MILLISECONDS, normalize_unit(synonym));
+        }
+        for synonym in ["s", "sec", "secs", "second", "SECONDS"] {
+            assert_eq!(UNIT_SECONDS, normalize_unit(synonym));
+        }
+        for synonym in ["B", "byte", "Bytes"] {
+            assert_eq!(UNIT_BYTES, normalize_unit(synonym));
+        }
+        for synonym in ["B/s", "Bps", "bytes/s", "Bytes per second"] {
+            assert_eq!(UNIT_BYTES_PER_SECOND, normalize_unit(synonym));
+        }
+    }
+
+    #[test]
+    fn normalize_keeps_symbols_case_sensitive() {
+        assert_eq!(UNIT_MEGABYTES, normalize_unit("MB"));
+        assert_eq!("mb", normalize_unit("mb"));
+        assert_eq!(UNIT_MINUTES, normalize_unit("min"));
+        assert_eq!(UNIT_MILLISECONDS, normalize_unit("ms"));
+    }
+
+    #[test]
+    fn normalize_keeps_unknown_units() {
+        assert_eq!("none", normalize_unit("none"));
+        assert_eq!("points/s", normalize_unit(" points/s"));
+    }
+}
@@ -1,9 +1,140 @@
-use rand::{thread_rng, Rng, distributions::Alphanumeric};
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    sync::{LockResult, PoisonError, RwLock},
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use postgres::GenericClient;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use crate::ensure_schema_table;
 
 const UNIQUE_ID_LENGTH: usize = 16;
+/// How often `gen_unused_ids` generates new IDs if the generated IDs are already in use
+const MAX_UNIQUE_ID_ATTEMPTS: usize = 8;
 
-/// Generates a unique ID that matches the database datatype (varchar(16))
-pub(crate) fn gen_unique_id() -> String {
+/// Function that generates the unique IDs for new experiments, variables and runs
+pub type UniqueIdGenerator = fn() -> String;
+
+static UNIQUE_ID_GENERATOR: RwLock<UniqueIdGenerator> = RwLock::new(gen_alphanumeric_id);
+
+/// The default generator for unique IDs. Generates a random alphanumeric ID that matches the default database datatype
+/// (varchar(16)). There are 62^16 (about 4.8 * 10^28) possible IDs, so even after generating a billion IDs, the chance
+/// that any two of them are equal is below 10^-10. Collisions with existing IDs are still detected when inserting new
+/// rows, and a new ID is generated in that case
+pub fn gen_alphanumeric_id() -> String {
     let mut rng = thread_rng();
-    (0..UNIQUE_ID_LENGTH).map(|_| rng.sample(Alphanumeric) as char).collect()
-}
\ No newline at end of file
+    (0..UNIQUE_ID_LENGTH)
+        .map(|_| rng.sample(Alphanumeric) as char)
+        .collect()
+}
+
+/// Sets the function that is used to generate unique IDs for all new database entries. This is meant to be called
+/// once at startup, before any data is written to the database. The default generator is `gen_alphanumeric_id`. The
+/// ID columns of the default database schema hold at most 16 characters, if the generator produces longer IDs, call
+/// `widen_id_columns` first
+pub fn set_unique_id_generator(generator: UniqueIdGenerator) {
+    let mut current_generator = ignore_poison(UNIQUE_ID_GENERATOR.write());
+    *current_generator = generator;
+}
+
+/// Function that returns the current time, used as the timestamp of new runs
+pub type Clock = fn() -> SystemTime;
+
+thread_local! {
+    static CLOCK: Cell<Clock> = Cell::new(SystemTime::now);
+}
+
+/// Sets the clock that is used for the timestamps of new runs on the current thread. Replacing the clock with a
+/// function that returns fixed dates makes tests that depend on run timestamps deterministic. Since the clock is
+/// thread-local, it does not affect concurrently running tests. The default clock is `SystemTime::now`
+pub fn set_clock(clock: Clock) {
+    CLOCK.with(|current_clock| current_clock.set(clock));
+}
+
+/// Returns the current time according to the clock of the current thread (see `set_clock`)
+pub(crate) fn now() -> SystemTime {
+    CLOCK.with(|clock| clock.get())()
+}
+
+/// Returns the guard (or value) of a lock even if the lock is poisoned. A lock is poisoned if another thread panicked
+/// while holding it. None of the locks in this crate are held while their data is in an inconsistent state, so the data
+/// is still valid and a single panic should not make every later call panic as well
+pub(crate) fn ignore_poison<T>(result: LockResult<T>) -> T {
+    result.unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Generates a unique ID using the current unique ID generator
+pub(crate) fn gen_unique_id() -> String {
+    let generator = *ignore_poison(UNIQUE_ID_GENERATOR.read());
+    generator()
+}
+
+/// Generates `count` IDs for new rows of `table` using the current unique ID generator. IDs that already exist in the
+/// `id` column of `table`, or that occur more than once within the generated IDs, are replaced by newly generated IDs.
+/// Fails if no unused IDs are found after a few attempts, which indicates that the unique ID generator is not random
+pub(crate) fn gen_unused_ids<C: GenericClient>(
+    table: &str,
+    count: usize,
+    client: &mut C,
+) -> Result<Vec<String>> {
+    ensure_schema_table(table)?;
+    let mut ids = (0..count).map(|_| gen_unique_id()).collect::<Vec<_>>();
+    for _ in 0..MAX_UNIQUE_ID_ATTEMPTS {
+        let mut used_ids = client
+            .query(
+                &format!("SELECT id FROM {table} WHERE id = ANY($1)"),
+                &[&ids],
+            )
+            .with_context(|| format!("Failed to query existing IDs of table {table}"))?
+            .into_iter()
+            .map(|row| row.get::<_, String>(0))
+            .collect::<HashSet<_>>();
+        let mut has_collisions = false;
+        for id in &mut ids {
+            // `insert` returns false for IDs that are already used, either in the table or by a previous ID
+            if !used_ids.insert(id.clone()) {
+                *id = gen_unique_id();
+                has_collisions = true;
+            }
+        }
+        if !has_collisions {
+            return Ok(ids);
+        }
+    }
+    bail!("Could not generate unused IDs for table {table} after {MAX_UNIQUE_ID_ATTEMPTS} attempts. Make sure that the unique ID generator produces random IDs")
+}
+
+/// Generates a single ID for a new row of `table`, see `gen_unused_ids`
+pub(crate) fn gen_unused_id<C: GenericClient>(table: &str, client: &mut C) -> Result<String> {
+    let mut ids = gen_unused_ids(table, 1, client)?;
+    Ok(ids.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn poisoned_lock_is_recovered() {
+        let lock = Mutex::new(1);
+        let result = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let mut value = lock.lock().unwrap();
+                    *value = 2;
+                    panic!("Panic while holding the lock");
+                })
+                .join()
+        });
+        assert!(result.is_err());
+        assert!(lock.is_poisoned());
+
+        *ignore_poison(lock.lock()) += 1;
+        assert_eq!(3, ignore_poison(lock.into_inner()));
+    }
+}
@@ -1,12 +1,16 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use postgres::{GenericClient, Row};
 
-use crate::gen_unique_id;
+use crate::{canonical_unit, execute, gen_unused_id};
 
 /// Template for a variable definition that is part of an experiment
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, Clone)]
 pub struct VariableTemplate {
     name: Cow<'static, str>,
     description: Cow<'static, str>,
@@ -14,12 +18,16 @@ pub struct VariableTemplate {
 }
 
 impl VariableTemplate {
-    /// Creates a new template for a variable with the given name, description and unit
+    /// Creates a new template for a variable with the given name, description and unit. The unit is stored as given and
+    /// normalized using `normalize_unit` when it is read through `unit`, so common synonyms such as "msec" and
+    /// "milliseconds" both become "ms" and templates with synonymous units compare equal. The `UNIT_...` constants
+    /// contain the canonical symbols for all well-known units
     pub const fn new(
         name: Cow<'static, str>,
         description: Cow<'static, str>,
         unit: Cow<'static, str>,
     ) -> Self {
+        // Normalizing is not possible in a const fn, so the unit is normalized when it is read instead
         Self {
             name,
             description,
@@ -27,30 +35,65 @@ impl VariableTemplate {
         }
     }
 
+    /// Returns a builder for a new variable template. This is an alternative to `VariableTemplate::new` that makes it
+    /// harder to mix up the name, description and unit
+    ///
+    /// ```
+    /// use experiment_archiver::{VariableTemplate, UNIT_MILLISECONDS};
+    ///
+    /// let runtime = VariableTemplate::builder()
+    ///     .name("Runtime")
+    ///     .description("The runtime of the experiment")
+    ///     .unit("milliseconds")
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!("Runtime", runtime.name());
+    /// assert_eq!(UNIT_MILLISECONDS, runtime.unit());
+    ///
+    /// // The name is required, description and unit default to empty strings
+    /// assert!(VariableTemplate::builder().description("No name").build().is_err());
+    /// let dataset = VariableTemplate::builder().name("Dataset").build().unwrap();
+    /// assert_eq!("", dataset.unit());
+    /// ```
+    pub fn builder() -> VariableTemplateBuilder {
+        VariableTemplateBuilder::default()
+    }
+
     /// The name of this variable template
     pub fn name(&self) -> &str {
         &self.name
     }
 
-    /// The description of this variable template
+    /// The description of this variable template. Variables without a description have an empty description, which is
+    /// stored as NULL in the database
     pub fn description(&self) -> &str {
         &self.description
     }
 
-    /// The unit of this variable template
+    /// The unit of this variable template, normalized using `normalize_unit`
     pub fn unit(&self) -> &str {
-        &self.unit
+        canonical_unit(&self.unit)
     }
 
-    /// Insert this VariableTemplate into the database and return the corresponding variable
+    /// Insert this VariableTemplate into the database and return the corresponding variable. If a concurrent transaction
+    /// inserted a variable with the same name in the meantime, that variable is returned instead
     pub(crate) fn insert_into_db<C: GenericClient>(&self, client: &mut C) -> Result<Variable> {
-        let variable_id = gen_unique_id();
-        let changed_rows = client
-            .execute(
-                "INSERT INTO variables VALUES ($1, $2, $3, $4)",
-                &[&variable_id, &self.name, &self.description, &self.unit],
-            )
-            .context("Failed to execute INSERT statement")?;
+        let variable_id = gen_unused_id("variables", client)?;
+        let description = (!self.description.is_empty()).then_some(&*self.description);
+        let changed_rows = execute(
+            client,
+            "INSERT INTO variables VALUES ($1, $2, $3, $4) ON CONFLICT (name) DO NOTHING",
+            &[&variable_id, &self.name, &description, &self.unit()],
+        )
+        .context("Failed to execute INSERT statement")?;
+        if changed_rows == 0 {
+            return self.fetch_from_db(client)?.ok_or_else(|| {
+                anyhow!(
+                    "Variable {} already exists, but could not be fetched",
+                    self.name
+                )
+            });
+        }
         if changed_rows != 1 {
             bail!("Unexpected number of affected rows ({})", changed_rows);
         }
@@ -72,7 +115,7 @@ impl VariableTemplate {
         if rows.len() > 1 {
             bail!("More than one variable with name {} found, this should be impossible. Make sure the database schema sets variable names as unique!", self.name);
         }
-        if rows.len() == 0 {
+        if rows.is_empty() {
             return Ok(None);
         }
         let row = &rows[0];
@@ -83,6 +126,77 @@ impl VariableTemplate {
     }
 }
 
+// Templates are compared by their normalized unit, so templates with synonymous units (e.g. "ms" and "msec") are equal
+impl PartialEq for VariableTemplate {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.description == other.description
+            && self.unit() == other.unit()
+    }
+}
+
+impl Eq for VariableTemplate {}
+
+impl Hash for VariableTemplate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.description.hash(state);
+        self.unit().hash(state);
+    }
+}
+
+/// Formats the variable as its name, followed by the description in parentheses and the unit in brackets, e.g.
+/// `Runtime (The runtime of the experiment) [ms]`. An empty description or unit is omitted
+impl Display for VariableTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.description.is_empty() {
+            write!(f, " ({})", self.description)?;
+        }
+        if !self.unit().is_empty() {
+            write!(f, " [{}]", self.unit())?;
+        }
+        Ok(())
+    }
+}
+
+/// Builder for a `VariableTemplate`, created through `VariableTemplate::builder`
+#[derive(Debug, Default, Clone)]
+pub struct VariableTemplateBuilder {
+    name: Option<Cow<'static, str>>,
+    description: Cow<'static, str>,
+    unit: Cow<'static, str>,
+}
+
+impl VariableTemplateBuilder {
+    /// Sets the name of the variable. This is required
+    pub fn name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the description of the variable
+    pub fn description(mut self, description: impl Into<Cow<'static, str>>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the unit of the variable. Like in `VariableTemplate::new`, the unit is stored as given and normalized when it
+    /// is read through `VariableTemplate::unit`
+    pub fn unit(mut self, unit: impl Into<Cow<'static, str>>) -> Self {
+        self.unit = unit.into();
+        self
+    }
+
+    /// Builds the `VariableTemplate`. Returns an error if no name was set
+    pub fn build(self) -> Result<VariableTemplate> {
+        let name = self
+            .name
+            .ok_or_else(|| anyhow!("A variable template requires a name"))?;
+        Ok(VariableTemplate::new(name, self.description, self.unit))
+    }
+}
+
 /// Variable definition after inserting into the DB or fetching from the DB
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Variable {
@@ -102,6 +216,12 @@ impl Variable {
     }
 }
 
+impl Display for Variable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.template.fmt(f)
+    }
+}
+
 impl TryFrom<&'_ Row> for Variable {
     type Error = anyhow::Error;
 
@@ -110,7 +230,7 @@ impl TryFrom<&'_ Row> for Variable {
         let name: String = value
             .try_get("name")
             .context("name field not found in row")?;
-        let description: String = value
+        let description: Option<String> = value
             .try_get("description")
             .context("description field not found in row")?;
         let unit: String = value
@@ -120,9 +240,43 @@ impl TryFrom<&'_ Row> for Variable {
             id,
             template: VariableTemplate {
                 name: name.into(),
-                description: description.into(),
+                description: description.unwrap_or_default().into(),
                 unit: unit.into(),
             },
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_template_in_const() {
+        const RUNTIME: VariableTemplate = VariableTemplate::new(
+            Cow::Borrowed("Runtime"),
+            Cow::Borrowed("The runtime"),
+            Cow::Borrowed("msec"),
+        );
+        assert_eq!("ms", RUNTIME.unit());
+        assert_eq!(
+            VariableTemplate::new("Runtime".into(), "The runtime".into(), "ms".into()),
+            RUNTIME
+        );
+    }
+
+    #[test]
+    fn display_variable_template() {
+        let runtime = VariableTemplate::new("Runtime".into(), "The runtime".into(), "msec".into());
+        assert_eq!("Runtime (The runtime) [ms]", runtime.to_string());
+
+        let dataset = VariableTemplate::new("Dataset".into(), "".into(), "".into());
+        assert_eq!("Dataset", dataset.to_string());
+
+        let size = VariableTemplate::new("Size".into(), "".into(), "bytes".into());
+        assert_eq!("Size [B]", size.to_string());
+
+        let notes = VariableTemplate::new("Notes".into(), "Remarks".into(), "".into());
+        assert_eq!("Notes (Remarks)", notes.to_string());
+    }
+}
@@ -40,7 +40,8 @@ CREATE TABLE public.experiment_runs (
     runnumber integer NOT NULL,
     experimentid character varying(16) NOT NULL,
     id character varying(16) NOT NULL,
-    "timestamp" timestamp without time zone
+    "timestamp" timestamp without time zone,
+    label text
 );
 
 
@@ -60,6 +61,18 @@ CREATE TABLE public.experiments (
 
 ALTER TABLE public.experiments OWNER TO postgres;
 
+--
+-- Name: experiment_tags; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.experiment_tags (
+    experiment_id character varying(16) NOT NULL,
+    tag text NOT NULL
+);
+
+
+ALTER TABLE public.experiment_tags OWNER TO postgres;
+
 --
 -- Name: measurements; Type: TABLE; Schema: public; Owner: postgres
 --
@@ -69,7 +82,8 @@ CREATE TABLE public.measurements (
     variableid character varying(16),
     runid character varying(16),
     value text,
-    "timestamp" timestamp without time zone
+    "timestamp" timestamp without time zone,
+    numeric_value double precision
 );
 
 
@@ -97,6 +111,14 @@ ALTER TABLE ONLY public.experiment_runs
     ADD CONSTRAINT experiment_runs_pkey PRIMARY KEY (id);
 
 
+--
+-- Name: experiment_runs experiment_runs_experimentid_label_key; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.experiment_runs
+    ADD CONSTRAINT experiment_runs_experimentid_label_key UNIQUE (experimentid, label);
+
+
 --
 -- Name: experiments experiments_name_key; Type: CONSTRAINT; Schema: public; Owner: postgres
 --
@@ -113,6 +135,14 @@ ALTER TABLE ONLY public.experiments
     ADD CONSTRAINT experiments_pkey PRIMARY KEY (id);
 
 
+--
+-- Name: experiment_tags experiment_tags_pkey; Type: CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.experiment_tags
+    ADD CONSTRAINT experiment_tags_pkey PRIMARY KEY (experiment_id, tag);
+
+
 --
 -- Name: variables variables_name_key; Type: CONSTRAINT; Schema: public; Owner: postgres
 --
@@ -137,6 +167,14 @@ ALTER TABLE ONLY public.experiment_variables
     ADD CONSTRAINT experiment_variables_experiment_id_fkey FOREIGN KEY (experiment_id) REFERENCES public.experiments(id);
 
 
+--
+-- Name: experiment_tags experiment_tags_experiment_id_fkey; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.experiment_tags
+    ADD CONSTRAINT experiment_tags_experiment_id_fkey FOREIGN KEY (experiment_id) REFERENCES public.experiments(id);
+
+
 --
 -- Name: experiment_variables experiment_variables_variable_id_fkey; Type: FK CONSTRAINT; Schema: public; Owner: postgres
 --
@@ -0,0 +1,31 @@
+/**
+ * Tests that run the CLI binary. The connection to the database is given through the connection flags, which bypass
+ * the configuration file of the user, so these tests neither read nor write anything in the home directory
+ */
+use std::process::Command;
+
+/// Exit code of the CLI if the database is not reachable
+const EXIT_DATABASE_CONNECTION: i32 = 3;
+
+#[test]
+fn unreachable_database_has_exit_code() {
+    // No database is listening on port 1, so connecting must fail
+    let output = Command::new(env!("CARGO_BIN_EXE_phd-ex-cli"))
+        .args([
+            "list-experiments",
+            "--user",
+            "user",
+            "--password",
+            "password",
+            "--host",
+            "127.0.0.1",
+            "--port",
+            "1",
+            "--dbname",
+            "db",
+        ])
+        .env_remove("PSQL_CONNECT_RETRIES")
+        .output()
+        .expect("Failed to run CLI");
+    assert_eq!(Some(EXIT_DATABASE_CONNECTION), output.status.code());
+}
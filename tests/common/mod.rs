@@ -0,0 +1,30 @@
+//! Helpers that are shared between the test binaries. Not every binary uses every helper
+#![allow(dead_code)]
+
+use anyhow::Result;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+/// Connects directly to the test database, bypassing the library. Used to create data that the library itself would
+/// never write, or to change the schema of the test database
+pub fn connect_to_test_db() -> Result<postgres::Client> {
+    connect_to_database(&std::env::var("PSQL_DBNAME")?)
+}
+
+/// Connects directly to the database with the given name on the server of the test database
+pub fn connect_to_database(dbname: &str) -> Result<postgres::Client> {
+    let client = postgres::Config::new()
+        .host(&std::env::var("PSQL_HOST")?)
+        .port(std::env::var("PSQL_PORT")?.parse()?)
+        .user(&std::env::var("PSQL_USER")?)
+        .password(std::env::var("PSQL_PWD")?)
+        .dbname(dbname)
+        .connect(postgres::NoTls)?;
+    Ok(client)
+}
+
+pub fn random_string(length: usize) -> String {
+    let mut rng = thread_rng();
+    (0..length)
+        .map(|_| rng.sample(Alphanumeric) as char)
+        .collect()
+}
@@ -0,0 +1,124 @@
+/**
+ * Tests for the dry-run mode. Dry-run mode is enabled through an environment variable, which would affect concurrently
+ * running tests. Therefore these tests live in a separate test binary
+ */
+mod common;
+
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use common::random_string;
+use experiment_archiver::{is_dry_run, Experiment, VariableTemplate};
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Tests in this binary change the dry-run environment variable, so they must not run concurrently
+static DRY_RUN_LOCK: Mutex<()> = Mutex::new(());
+
+/// Logger that stores all log messages, so that tests can check what was logged
+struct CapturingLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.messages
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+    messages: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn dry_run_does_not_write_to_database() -> Result<()> {
+    let _lock = DRY_RUN_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    std::env::set_var("PSQL_DRY_RUN", "1");
+    assert!(is_dry_run());
+    let new_experiment_name = random_string(16);
+    let dry_run_result = (|| -> Result<()> {
+        Experiment::new(
+            new_experiment_name.clone(),
+            random_string(32),
+            random_string(16),
+            Default::default(),
+        )?;
+        experiment.run(|context| {
+            context.add_value_by_name(variable.name(), 1);
+            Ok(())
+        })?;
+        experiment.run_many([[(variable.name(), 2)], [(variable.name(), 3)]])?;
+        experiment.add_tag("dry-run")?;
+        Ok(())
+    })();
+    std::env::remove_var("PSQL_DRY_RUN");
+    dry_run_result.context("Writing in dry-run mode must succeed")?;
+
+    assert!(Experiment::from_name(&new_experiment_name)?.is_none());
+    assert!(experiment.run_headers()?.is_empty());
+    assert!(experiment.tags()?.is_empty());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_logs_statements() -> Result<()> {
+    let _lock = DRY_RUN_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    log::set_logger(&LOGGER).expect("No other logger must be set");
+    log::set_max_level(LevelFilter::Debug);
+
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    LOGGER.messages.lock().unwrap().clear();
+
+    std::env::set_var("PSQL_DRY_RUN", "1");
+    let dry_run_result = experiment.run(|context| {
+        context.add_value_by_name(variable.name(), "dry-run-value");
+        Ok(())
+    });
+    std::env::remove_var("PSQL_DRY_RUN");
+    dry_run_result.context("Writing in dry-run mode must succeed")?;
+
+    let messages = LOGGER.messages.lock().unwrap().clone();
+    assert!(messages.iter().any(|message| {
+        message.starts_with("Dry run: INSERT INTO experiment_runs")
+            && message.contains(experiment.id())
+    }));
+    assert!(messages.iter().any(|message| {
+        message.starts_with("Dry run: INSERT INTO measurements")
+            && message.contains("\"dry-run-value\"")
+    }));
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
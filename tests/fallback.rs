@@ -0,0 +1,70 @@
+/**
+ * Tests for the fallback file. These tests have to make the database unreachable by changing the connection
+ * environment variables, which would break concurrently running tests. Therefore they live in a separate test binary
+ * and must not be run in parallel with each other
+ */
+mod common;
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use common::random_string;
+use experiment_archiver::{replay_fallback_file, Experiment, VariableTemplate};
+
+#[test]
+fn run_is_written_to_fallback_file_if_database_is_unreachable() -> Result<()> {
+    let variables: HashSet<_> = (0..2)
+        .map(|_| {
+            VariableTemplate::new(
+                random_string(16).into(),
+                random_string(32).into(),
+                random_string(8).into(),
+            )
+        })
+        .collect();
+    let mut experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        variables.clone(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    let fallback_file =
+        std::env::temp_dir().join(format!("experiment-fallback-{}.ndjson", random_string(8)));
+    experiment.set_fallback_file(Some(fallback_file.clone()));
+
+    let port = std::env::var("PSQL_PORT")?;
+    std::env::set_var("PSQL_PORT", "1");
+    let run_result = experiment.run(|context| {
+        for variable in &variables {
+            context.add_value_by_name(variable.name(), "a value with spaces");
+        }
+        Ok(())
+    });
+    std::env::set_var("PSQL_PORT", port);
+    let run_id = run_result.context("Run must succeed by writing to the fallback file")?;
+
+    let contents = std::fs::read_to_string(&fallback_file)?;
+    assert_eq!(1, contents.lines().count());
+    assert!(contents.contains(&run_id));
+    assert!(experiment.run_from_id(&run_id)?.is_none());
+
+    let replayed_run_ids = replay_fallback_file(&fallback_file)?;
+    assert_eq!(vec![run_id.clone()], replayed_run_ids);
+    assert!(std::fs::read_to_string(&fallback_file)?.is_empty());
+
+    let run = experiment
+        .run_from_id(&run_id)?
+        .expect("Replayed run not found in database");
+    assert_eq!(variables.len(), run.measurements().len());
+    assert!(run
+        .measurements()
+        .iter()
+        .all(|measurement| measurement.value() == Some("a value with spaces")));
+
+    std::fs::remove_file(&fallback_file)?;
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
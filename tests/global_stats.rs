@@ -0,0 +1,65 @@
+/**
+ * Tests for the global statistics of the database. These tests compare the exact counts before and after inserting
+ * data, which only works if no other test writes to the database concurrently. Therefore they live in a separate test
+ * binary
+ */
+mod common;
+
+use anyhow::{Context, Result};
+use common::random_string;
+use experiment_archiver::{Experiment, GlobalStats, VariableTemplate};
+
+#[test]
+fn global_stats_count_new_data() -> Result<()> {
+    const NUM_VARIABLES: usize = 3;
+    const NUM_RUNS: usize = 2;
+
+    let before = GlobalStats::fetch().context("Failed to fetch global stats")?;
+
+    let variables = (0..NUM_VARIABLES)
+        .map(|_| {
+            VariableTemplate::new(
+                random_string(16).into(),
+                random_string(32).into(),
+                random_string(8).into(),
+            )
+        })
+        .collect();
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        variables,
+    )
+    .context("Failed to create new Experiment")?;
+    for _ in 0..NUM_RUNS {
+        experiment
+            .run(|context| {
+                for variable in experiment.variables() {
+                    context.add_value_by_name(variable.template().name(), random_string(8));
+                }
+                Ok(())
+            })
+            .context("Experiment run failed")?;
+    }
+
+    let after_insert = GlobalStats::fetch().context("Failed to fetch global stats")?;
+    assert_eq!(before.experiments + 1, after_insert.experiments);
+    assert_eq!(before.variables + NUM_VARIABLES, after_insert.variables);
+    assert_eq!(before.runs + NUM_RUNS, after_insert.runs);
+    assert_eq!(
+        before.measurements + NUM_RUNS * NUM_VARIABLES,
+        after_insert.measurements
+    );
+
+    experiment.delete_from_database()?;
+
+    // Variables can be shared between experiments, so they are kept when an experiment is deleted
+    let after_delete = GlobalStats::fetch().context("Failed to fetch global stats")?;
+    assert_eq!(before.experiments, after_delete.experiments);
+    assert_eq!(after_insert.variables, after_delete.variables);
+    assert_eq!(before.runs, after_delete.runs);
+    assert_eq!(before.measurements, after_delete.measurements);
+
+    Ok(())
+}
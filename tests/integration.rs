@@ -5,19 +5,22 @@
  * To prevent collisions, all test data uses random IDs, the chance of ID collisions will be very low since IDs
  * are 16-character strings, so something like 1 in 62^16
  */
-use std::collections::{HashMap, HashSet};
+mod common;
 
-use experiment_archiver::{self, Experiment, VariableTemplate};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
 
-use anyhow::{Context, Result};
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use experiment_archiver::{
+    self, add_experiment_tags_table, add_numeric_value_column, add_run_label_column,
+    missing_schema_columns, ping, schema_exists, set_clock, ComparisonOperator, ExarError,
+    Experiment, ExperimentArchive, MeasurementPredicate, VariableTemplate,
+};
 
-fn random_string(length: usize) -> String {
-    let mut rng = thread_rng();
-    (0..length)
-        .map(|_| rng.sample(Alphanumeric) as char)
-        .collect()
-}
+use anyhow::{Context, Result};
+use common::{connect_to_test_db, random_string};
 
 #[test]
 fn showcase() -> Result<()> {
@@ -86,6 +89,46 @@ fn new_experiment_is_added_to_db() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn empty_description_is_stored_as_null() -> Result<()> {
+    let name = random_string(16);
+    let researcher = random_string(16);
+    let variable_name = random_string(16);
+    let variables = HashSet::from([VariableTemplate::new(
+        variable_name.clone().into(),
+        "".into(),
+        "".into(),
+    )]);
+    let experiment = Experiment::new(
+        name.clone(),
+        "Empty description".to_owned(),
+        researcher.clone(),
+        variables.clone(),
+    )?;
+
+    let mut client = connect_to_test_db()?;
+    let row = client.query_one(
+        "SELECT description FROM variables WHERE name = $1",
+        &[&variable_name],
+    )?;
+    assert_eq!(None, row.get::<_, Option<String>>(0));
+
+    // The NULL description is read back as an empty description, so the known experiment still matches
+    let known_experiment =
+        Experiment::new(name, "Empty description".to_owned(), researcher, variables)?;
+    assert_eq!(experiment, known_experiment);
+    let variable = known_experiment
+        .variables()
+        .next()
+        .expect("Experiment must have a variable");
+    assert_eq!("", variable.template().description());
+    assert_eq!(variable_name, variable.to_string());
+
+    known_experiment.delete_from_database()?;
+
+    Ok(())
+}
+
 #[test]
 fn add_known_experiment() -> Result<()> {
     const NUM_VARIABLES: usize = 4;
@@ -157,7 +200,7 @@ fn new_experiment_run() -> Result<()> {
     let run_id = experiment
         .run(|context| {
             for (variable, value) in &expected_measurements_for_run {
-                context.add_value_by_name(variable.template().name(), &value);
+                context.add_value_by_name(variable.template().name(), value);
             }
 
             Ok(())
@@ -177,9 +220,648 @@ fn new_experiment_run() -> Result<()> {
         let expected_value = expected_measurements_for_run
             .get(measurement.variable())
             .expect("Unexpected variable");
-        assert_eq!(expected_value, measurement.value());
+        assert_eq!(Some(expected_value.as_str()), measurement.value());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn experiment_from_builder() -> Result<()> {
+    let name = random_string(16);
+    let runtime = random_string(16);
+    let notes = random_string(16);
+    let experiment = Experiment::builder()
+        .name(name.clone())
+        .description("An experiment defined through the builder")
+        .researcher("Jane Doe")
+        .variable(runtime.clone(), "Runtime", "milliseconds")
+        .optional_variable(notes.clone(), "", "")
+        .build()?;
+    assert_eq!(name, experiment.name());
+    assert_eq!(2, experiment.variables().count());
+    assert!(!experiment.is_variable_optional(&runtime));
+    assert!(experiment.is_variable_optional(&notes));
+
+    assert!(Experiment::builder()
+        .description("No name")
+        .build()
+        .is_err());
+    assert!(Experiment::builder()
+        .name(random_string(16))
+        .variable(runtime.clone(), "", "")
+        .optional_variable(runtime.clone(), "", "")
+        .build()
+        .is_err());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn experiment_from_json() -> Result<()> {
+    let name = random_string(16);
+    let runtime = random_string(16);
+    let notes = random_string(16);
+    let definition = format!(
+        r#"{{
+            "name": "{name}",
+            "description": "An experiment defined in JSON",
+            "researcher": "Jane Doe",
+            "variables": [
+                {{ "name": "{runtime}", "description": "Runtime", "unit": "milliseconds" }},
+                {{ "name": "{notes}", "optional": true }}
+            ]
+        }}"#
+    );
+
+    let experiment = Experiment::from_json(definition.as_bytes())?;
+    assert_eq!(name, experiment.name());
+    assert_eq!("Jane Doe", experiment.researcher());
+    let runtime_variable = experiment
+        .variables()
+        .find(|variable| variable.template().name() == runtime)
+        .expect("Runtime variable not found");
+    assert_eq!("ms", runtime_variable.template().unit());
+    assert!(experiment.is_variable_optional(&notes));
+    assert!(!experiment.is_variable_optional(&runtime));
+
+    // The same definition refers to the same experiment
+    let same_experiment = Experiment::from_json(definition.as_bytes())?;
+    assert_eq!(experiment.id(), same_experiment.id());
+
+    assert!(Experiment::from_json(r#"{ "name": "missing fields" }"#.as_bytes()).is_err());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn duplicate_variable_names_are_rejected() -> Result<()> {
+    let variable_name = random_string(16);
+    let variables: HashSet<_> = [
+        VariableTemplate::new(
+            variable_name.clone().into(),
+            "First description".into(),
+            "ms".into(),
+        ),
+        VariableTemplate::new(
+            variable_name.clone().into(),
+            "Second description".into(),
+            "ms".into(),
+        ),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(2, variables.len());
+
+    let name = random_string(16);
+    let error = Experiment::new(
+        name.clone(),
+        random_string(32),
+        random_string(16),
+        variables,
+    )
+    .expect_err("Experiment with duplicate variable names must be rejected");
+    assert!(error.to_string().contains(&variable_name));
+    assert!(Experiment::from_name(&name)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn required_variable_can_be_marked_as_missing() -> Result<()> {
+    let runtime = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let host = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [runtime.clone(), host.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    let missing_run_id = experiment.run(|context| {
+        context.add_value_by_name(host.name(), "node1");
+        context.add_missing(runtime.name());
+        Ok(())
+    })?;
+    // A value that is added after marking the variable as missing replaces the missing value
+    let replaced_run_id = experiment.run(|context| {
+        context.add_value_by_name(host.name(), "node1");
+        context.add_missing(runtime.name());
+        context.add_value_by_name(runtime.name(), 42);
+        Ok(())
+    })?;
+    // A value that was added before is removed again
+    let removed_run_id = experiment.run(|context| {
+        context.add_value_by_name(runtime.name(), 42);
+        context.add_missing(runtime.name());
+        context.add_missing(host.name());
+        Ok(())
+    })?;
+
+    let value_of_runtime = |run_id: &str| -> Result<Option<String>> {
+        Ok(experiment
+            .measurements_for_run(run_id)?
+            .iter()
+            .find(|measurement| measurement.variable().template().name() == runtime.name())
+            .expect("Measurement for runtime not found")
+            .value()
+            .map(str::to_owned))
+    };
+    assert_eq!(None, value_of_runtime(&missing_run_id)?);
+    assert_eq!(Some("42".to_owned()), value_of_runtime(&replaced_run_id)?);
+    assert_eq!(None, value_of_runtime(&removed_run_id)?);
+    assert_eq!(2, experiment.measurements_for_run(&removed_run_id)?.len());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn derived_variables_are_computed() -> Result<()> {
+    // Random names may start with a digit, so they are quoted in expressions
+    let bytes = random_string(16);
+    let runtime = random_string(16);
+    let read = random_string(16);
+    let write = random_string(16);
+    let throughput = random_string(16);
+    let total = random_string(16);
+    let total_per_second = random_string(16);
+    let experiment = Experiment::builder()
+        .name(random_string(16))
+        .researcher(random_string(16))
+        .variable(bytes.clone(), "", "B")
+        .variable(runtime.clone(), "", "s")
+        .variable(read.clone(), "", "")
+        .optional_variable(write.clone(), "", "")
+        .derived_variable(
+            throughput.clone(),
+            "",
+            "B/s",
+            format!("\"{bytes}\" / \"{runtime}\""),
+        )
+        .derived_variable(
+            total_per_second.clone(),
+            "",
+            "",
+            format!("\"{total}\" / \"{runtime}\""),
+        )
+        .derived_variable(total.clone(), "", "", format!("\"{read}\" + \"{write}\""))
+        .build()?;
+    assert!(experiment.is_variable_derived(&throughput));
+    assert!(!experiment.is_variable_derived(&bytes));
+
+    let complete_run_id = experiment.run(|context| {
+        context.add_value_by_name(&bytes, 3000);
+        context.add_value_by_name(&runtime, 1.5);
+        context.add_value_by_name(&read, 5);
+        context.add_value_by_name(&write, 7);
+        Ok(())
+    })?;
+    // Without a value for `write`, the derived variables that depend on it have no value
+    let incomplete_run_id = experiment.run_many([[
+        (bytes.as_str(), "10"),
+        (runtime.as_str(), "4"),
+        (read.as_str(), "5"),
+    ]])?[0]
+        .clone();
+
+    let values_of_run = |run_id: &str| -> Result<HashMap<String, Option<String>>> {
+        Ok(experiment
+            .measurements_for_run(run_id)?
+            .iter()
+            .map(|measurement| {
+                (
+                    measurement.variable().template().name().to_owned(),
+                    measurement.value().map(str::to_owned),
+                )
+            })
+            .collect())
+    };
+    let complete_values = values_of_run(&complete_run_id)?;
+    assert_eq!(Some("2000".to_owned()), complete_values[&throughput]);
+    assert_eq!(Some("12".to_owned()), complete_values[&total]);
+    assert_eq!(Some("8".to_owned()), complete_values[&total_per_second]);
+    let incomplete_values = values_of_run(&incomplete_run_id)?;
+    assert_eq!(Some("2.5".to_owned()), incomplete_values[&throughput]);
+    assert_eq!(None, incomplete_values[&total]);
+    assert_eq!(None, incomplete_values[&total_per_second]);
+
+    // Derived values can't be added manually, and their inputs have to be numbers
+    assert!(experiment
+        .run(|context| {
+            context.add_value_by_name(&bytes, 3000);
+            context.add_value_by_name(&runtime, 1.5);
+            context.add_value_by_name(&read, 5);
+            context.add_value_by_name(&throughput, 1);
+            Ok(())
+        })
+        .is_err());
+    let error = experiment
+        .run_many([[
+            (bytes.as_str(), "many"),
+            (runtime.as_str(), "4"),
+            (read.as_str(), "5"),
+        ]])
+        .unwrap_err();
+    assert_eq!(
+        Some(&ExarError::NotANumber {
+            variable: bytes.clone(),
+            value: "many".to_owned()
+        }),
+        ExarError::find_in(&error)
+    );
+    assert_eq!(2, experiment.count_runs()?);
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn json_schema_describes_variables() -> Result<()> {
+    let runtime = random_string(16);
+    let dataset = random_string(16);
+    let notes = random_string(16);
+    let runs_per_second = random_string(16);
+    let experiment = Experiment::builder()
+        .name(random_string(16))
+        .description("Schema test")
+        .researcher(random_string(16))
+        .variable(runtime.clone(), "The runtime", "milliseconds")
+        .variable(dataset.clone(), "The dataset", "")
+        .optional_variable(notes.clone(), "Remarks", "")
+        .derived_variable(
+            runs_per_second.clone(),
+            "Runs per second",
+            "",
+            format!("1000 / \"{runtime}\""),
+        )
+        .build()?;
+
+    let schema = experiment.to_json_schema();
+    assert_eq!("object", schema["type"]);
+    assert_eq!(experiment.name(), schema["title"]);
+    assert_eq!("Schema test", schema["description"]);
+    let properties = schema["properties"]
+        .as_object()
+        .expect("Properties must be an object");
+    assert_eq!(4, properties.len());
+    assert_eq!(
+        serde_json::json!({ "description": "The runtime", "type": "number", "x-unit": "ms" }),
+        properties[&runtime]
+    );
+    assert_eq!(
+        serde_json::json!({ "description": "The dataset", "type": "string" }),
+        properties[&dataset]
+    );
+    assert_eq!("string", properties[&notes]["type"]);
+    assert_eq!("number", properties[&runs_per_second]["type"]);
+    assert_eq!(true, properties[&runs_per_second]["readOnly"]);
+
+    let mut expected_required = vec![runtime, dataset];
+    expected_required.sort();
+    assert_eq!(serde_json::json!(expected_required), schema["required"]);
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn invalid_derived_variables_are_rejected() -> Result<()> {
+    let a = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let b = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let c = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let mut experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [a.clone(), b.clone(), c.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    let quoted = |variable: &VariableTemplate| format!("\"{}\"", variable.name());
+
+    assert!(experiment
+        .set_variable_derived(a.name(), &format!("{} * 2", quoted(&a)))
+        .is_err());
+    assert!(experiment
+        .set_variable_derived(a.name(), &format!("\"{}\" * 2", random_string(16)))
+        .is_err());
+    assert!(experiment
+        .set_variable_derived(&random_string(16), &quoted(&b))
+        .is_err());
+    assert!(experiment
+        .set_variable_derived(a.name(), &format!("{} *", quoted(&b)))
+        .is_err());
+
+    // a depends on b, b on c, so c can't depend on a
+    experiment.set_variable_derived(a.name(), &format!("{} + 1", quoted(&b)))?;
+    experiment.set_variable_derived(b.name(), &format!("{} + 1", quoted(&c)))?;
+    assert!(experiment
+        .set_variable_derived(c.name(), &format!("{} + 1", quoted(&a)))
+        .is_err());
+    assert!(!experiment.is_variable_derived(c.name()));
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn errors_can_be_matched() -> Result<()> {
+    let runtime = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let host = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let name = random_string(16);
+    let description = random_string(32);
+    let researcher = random_string(16);
+    let experiment = Experiment::new(
+        name.clone(),
+        description.clone(),
+        researcher.clone(),
+        [runtime.clone(), host.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    let error = experiment
+        .run(|context| {
+            context.add_value_by_name(host.name(), "node1");
+            Ok(())
+        })
+        .unwrap_err();
+    assert_eq!(
+        Some(&ExarError::MissingValues(vec![runtime.name().to_owned()])),
+        ExarError::find_in(&error)
+    );
+    let error = experiment.run_many([[(host.name(), "node1")]]).unwrap_err();
+    assert_eq!(
+        Some(&ExarError::MissingValues(vec![runtime.name().to_owned()])),
+        ExarError::find_in(&error)
+    );
+
+    let unknown_variable = random_string(16);
+    let error = experiment
+        .run_many([[
+            (host.name(), "node1"),
+            (runtime.name(), "1"),
+            (unknown_variable.as_str(), "1"),
+        ]])
+        .unwrap_err();
+    assert_eq!(
+        Some(&ExarError::UnknownVariable {
+            experiment: name.clone(),
+            variable: unknown_variable
+        }),
+        ExarError::find_in(&error)
+    );
+
+    let error = Experiment::new(
+        name.clone(),
+        random_string(32),
+        researcher.clone(),
+        [runtime.clone(), host.clone()].into_iter().collect(),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        ExarError::find_in(&error),
+        Some(ExarError::ExperimentMismatch(_))
+    ));
+
+    let unknown_name = random_string(16);
+    let error = Experiment::override_existing(
+        unknown_name.clone(),
+        description,
+        researcher,
+        [runtime.clone()].into_iter().collect(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        Some(&ExarError::ExperimentNotFound(unknown_name)),
+        ExarError::find_in(&error)
+    );
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn optional_variable_can_be_omitted() -> Result<()> {
+    let required_variable = VariableTemplate::new(
+        random_string(16).into(),
+        random_string(32).into(),
+        random_string(8).into(),
+    );
+    let optional_variable = VariableTemplate::new(
+        random_string(16).into(),
+        random_string(32).into(),
+        random_string(8).into(),
+    );
+
+    let mut experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [required_variable.clone(), optional_variable.clone()]
+            .into_iter()
+            .collect(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    // Omitting a value is an error as long as the variable is required
+    assert!(experiment
+        .run(|context| {
+            context.add_value_by_name(required_variable.name(), 42);
+            Ok(())
+        })
+        .is_err());
+
+    experiment.set_variable_optional(optional_variable.name(), true)?;
+    assert!(experiment
+        .set_variable_optional("unknown variable", true)
+        .is_err());
+
+    let run_id = experiment
+        .run(|context| {
+            context.add_value_by_name(required_variable.name(), 42);
+            Ok(())
+        })
+        .context("Experiment run failed")?;
+
+    let measurements = experiment
+        .measurements_for_run(&run_id)
+        .context("Failed to get measurements for experiment run")?;
+    assert_eq!(2, measurements.len());
+    for measurement in &measurements {
+        if measurement.variable().template().name() == optional_variable.name() {
+            assert_eq!(None, measurement.value());
+        } else {
+            assert_eq!(Some("42"), measurement.value());
+        }
     }
 
+    let all_runs = experiment.all_runs().context("Failed to get all runs")?;
+    assert_eq!(1, all_runs.len());
+    assert_eq!(2, all_runs[0].measurements().len());
+
+    Ok(())
+}
+
+#[test]
+fn rename_experiment() -> Result<()> {
+    let variables: HashSet<_> = (0..2)
+        .map(|_| {
+            VariableTemplate::new(
+                random_string(16).into(),
+                random_string(32).into(),
+                random_string(8).into(),
+            )
+        })
+        .collect();
+
+    let old_name = random_string(16);
+    let new_name = random_string(16);
+    let mut experiment = Experiment::new(
+        old_name.clone(),
+        random_string(32),
+        random_string(16),
+        variables.clone(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    let run_id = experiment
+        .run(|context| {
+            for variable in &variables {
+                context.add_value_by_name(variable.name(), random_string(8));
+            }
+            Ok(())
+        })
+        .context("Experiment run failed")?;
+
+    experiment
+        .rename(&new_name)
+        .context("Failed to rename experiment")?;
+    assert_eq!(new_name, experiment.name());
+
+    assert!(Experiment::from_name(&old_name)?.is_none());
+    let renamed = Experiment::from_name(&new_name)?.expect("Renamed experiment not found");
+    assert_eq!(experiment.id(), renamed.id());
+    assert_eq!(2, renamed.variables().count());
+
+    let runs = renamed.all_runs()?;
+    assert_eq!(1, runs.len());
+    assert_eq!(run_id, runs[0].id());
+    assert_eq!(
+        renamed.id(),
+        Experiment::from_run_id(&run_id)?
+            .expect("Experiment for run not found")
+            .id()
+    );
+
+    // Renaming to the name of another experiment must fail and leave both experiments untouched
+    let other_name = random_string(16);
+    let other = Experiment::new(
+        other_name.clone(),
+        random_string(32),
+        random_string(16),
+        HashSet::new(),
+    )?;
+    assert!(experiment.rename(&other_name).is_err());
+    assert_eq!(new_name, experiment.name());
+    assert!(Experiment::from_name(&new_name)?.is_some());
+
+    experiment.delete_from_database()?;
+    other.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn override_existing_experiment() -> Result<()> {
+    let kept_variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let removed_variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let added_variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+
+    let name = random_string(16);
+    let experiment = Experiment::new(
+        name.clone(),
+        random_string(32),
+        random_string(16),
+        [kept_variable.clone(), removed_variable.clone()]
+            .into_iter()
+            .collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    let run_id = experiment.run(|context| {
+        context.add_value_by_name(kept_variable.name(), "kept");
+        context.add_value_by_name(removed_variable.name(), "removed");
+        Ok(())
+    })?;
+
+    let new_description = random_string(32);
+    let new_researcher = random_string(16);
+    let overridden = Experiment::override_existing(
+        name.clone(),
+        new_description.clone(),
+        new_researcher.clone(),
+        [kept_variable.clone(), added_variable.clone()]
+            .into_iter()
+            .collect(),
+    )
+    .context("Failed to override experiment")?;
+    assert_eq!(experiment.id(), overridden.id());
+
+    let fetched = Experiment::from_name(&name)?.expect("Overridden experiment not found");
+    assert_eq!(experiment.id(), fetched.id());
+    assert_eq!(new_description, fetched.description());
+    assert_eq!(new_researcher, fetched.researcher());
+    let mut variable_names = fetched
+        .variables()
+        .map(|variable| variable.template().name())
+        .collect::<Vec<_>>();
+    variable_names.sort_unstable();
+    let mut expected_variable_names = vec![kept_variable.name(), added_variable.name()];
+    expected_variable_names.sort_unstable();
+    assert_eq!(expected_variable_names, variable_names);
+
+    // The existing run keeps its value for the variable that is still part of the experiment
+    let run = fetched
+        .run_from_id(&run_id)?
+        .expect("Run of overridden experiment not found");
+    assert_eq!(
+        vec![(kept_variable.name(), Some("kept"))],
+        run.measurements()
+            .iter()
+            .map(|measurement| (
+                measurement.variable().template().name(),
+                measurement.value()
+            ))
+            .collect::<Vec<_>>()
+    );
+
+    // The experiment can be created with its new data now
+    Experiment::new(
+        name.clone(),
+        new_description,
+        new_researcher,
+        [kept_variable, added_variable].into_iter().collect(),
+    )?;
+
+    assert!(Experiment::override_existing(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        HashSet::new()
+    )
+    .is_err());
+
+    fetched.delete_from_database()?;
+
     Ok(())
 }
 
@@ -216,7 +898,7 @@ fn delete_experiment() -> Result<()> {
     experiment1
         .run(|context| {
             for (variable, value) in &expected_measurements_for_run {
-                context.add_value_by_name(variable.template().name(), &value);
+                context.add_value_by_name(variable.template().name(), value);
             }
 
             Ok(())
@@ -281,7 +963,7 @@ fn delete_runs() -> Result<()> {
         experiment1
             .run(|context| {
                 for (variable, value) in &expected_measurements_for_run {
-                    context.add_value_by_name(variable.template().name(), &value);
+                    context.add_value_by_name(variable.template().name(), value);
                 }
 
                 Ok(())
@@ -305,3 +987,1196 @@ fn delete_runs() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn runs_with_inconsistent_measurements() -> Result<()> {
+    const NUM_VARIABLES: usize = 3;
+    let variables = (0..NUM_VARIABLES)
+        .map(|_| {
+            VariableTemplate::new(
+                random_string(16).into(),
+                random_string(32).into(),
+                random_string(8).into(),
+            )
+        })
+        .collect();
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        variables,
+    )
+    .context("Failed to create new Experiment")?;
+    let run_id = experiment
+        .run(|context| {
+            for variable in experiment.variables() {
+                context.add_value_by_name(variable.template().name(), random_string(8));
+            }
+            Ok(())
+        })
+        .context("Experiment run failed")?;
+
+    // Simulate a run that was recorded while the experiment had different variables: One measurement is missing, and
+    // there is a measurement for a variable that is not part of the experiment
+    let mut client = connect_to_test_db()?;
+    let removed_variable = experiment.variables().next().unwrap();
+    client.execute(
+        "DELETE FROM measurements WHERE runid = $1 AND variableid = $2",
+        &[&run_id, &removed_variable.id()],
+    )?;
+    let foreign_variable_id = random_string(16);
+    client.execute(
+        "INSERT INTO variables VALUES ($1, $2, $3, $4)",
+        &[
+            &foreign_variable_id,
+            &random_string(16),
+            &random_string(32),
+            &random_string(8),
+        ],
+    )?;
+    client.execute(
+        "INSERT INTO measurements VALUES ($1, $2, $3, $4, $5)",
+        &[
+            &experiment.id(),
+            &foreign_variable_id,
+            &run_id,
+            &random_string(8),
+            &std::time::SystemTime::now(),
+        ],
+    )?;
+
+    let measurements = experiment
+        .measurements_for_run(&run_id)
+        .context("Failed to get measurements for run")?;
+    assert_eq!(NUM_VARIABLES - 1, measurements.len());
+
+    let all_runs = experiment.all_runs().context("Failed to get all runs")?;
+    assert_eq!(1, all_runs.len());
+    assert_eq!(NUM_VARIABLES - 1, all_runs[0].measurements().len());
+
+    Ok(())
+}
+
+#[test]
+fn runs_in_transaction() -> Result<()> {
+    const NUM_VARIABLES: usize = 2;
+    let variables = (0..NUM_VARIABLES)
+        .map(|_| {
+            VariableTemplate::new(
+                random_string(16).into(),
+                random_string(32).into(),
+                random_string(8).into(),
+            )
+        })
+        .collect();
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        variables,
+    )
+    .context("Failed to create new Experiment")?;
+    let run_func = |context: &experiment_archiver::RunContext| {
+        for variable in experiment.variables() {
+            context.add_value_by_name(variable.template().name(), random_string(8));
+        }
+        Ok(())
+    };
+
+    // A failing batch must not store any of its runs
+    const NUM_RUNS: usize = 3;
+    let res = experiment.with_transaction(|batch| {
+        for _ in 0..NUM_RUNS {
+            batch.run(run_func)?;
+        }
+        anyhow::bail!("Abort batch")
+    });
+    assert!(res.is_err());
+    assert!(experiment.all_runs()?.is_empty());
+
+    // A successful batch stores all runs with consecutive run numbers
+    let run_ids = experiment.with_transaction(|batch| {
+        for _ in 0..NUM_RUNS {
+            batch.run(run_func)?;
+        }
+        Ok(())
+    })?;
+    assert_eq!(NUM_RUNS, run_ids.len());
+    let all_runs = experiment.all_runs()?;
+    assert_eq!(
+        (1..=NUM_RUNS).collect::<Vec<_>>(),
+        all_runs
+            .iter()
+            .map(|run| run.run_number())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        run_ids,
+        all_runs
+            .iter()
+            .map(|run| run.id().to_owned())
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn durations_are_converted_to_variable_unit() -> Result<()> {
+    let runtime_ms = VariableTemplate::new(random_string(16).into(), "".into(), "msec".into());
+    let runtime_s = VariableTemplate::new(random_string(16).into(), "".into(), "s".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [runtime_ms.clone(), runtime_s.clone()]
+            .into_iter()
+            .collect(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    let run_id = experiment.run(|context| {
+        context.add_duration_by_name(runtime_ms.name(), Duration::from_micros(12_500));
+        context.add_duration_by_name(runtime_s.name(), Duration::from_millis(1500));
+        Ok(())
+    })?;
+
+    let run = experiment.run_from_id(&run_id)?.expect("Run not found");
+    let value_of = |name: &str| {
+        run.measurements()
+            .iter()
+            .find(|measurement| measurement.variable().template().name() == name)
+            .and_then(|measurement| measurement.value())
+            .map(|value| value.to_owned())
+    };
+    assert_eq!(Some("12.5".to_owned()), value_of(runtime_ms.name()));
+    assert_eq!(Some("1.5".to_owned()), value_of(runtime_s.name()));
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn runs_can_be_found_by_label() -> Result<()> {
+    // The test database already has the label column, so the migration must be a no-op
+    add_run_label_column()?;
+
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    let labeled_run_id = experiment.run(|context| {
+        context.add_value_by_name(variable.name(), "labeled");
+        context.set_label("baseline");
+        Ok(())
+    })?;
+    let unlabeled_run_id = experiment.run(|context| {
+        context.add_value_by_name(variable.name(), "unlabeled");
+        Ok(())
+    })?;
+
+    let run = experiment
+        .run_from_label("baseline")?
+        .expect("No run found for label");
+    assert_eq!(labeled_run_id, run.id());
+    assert_eq!(Some("baseline"), run.label());
+    assert_eq!(Some("labeled"), run.measurements()[0].value());
+    assert!(experiment.run_from_label("unknown")?.is_none());
+
+    let unlabeled_run = experiment
+        .run_from_id(&unlabeled_run_id)?
+        .expect("Run not found");
+    assert_eq!(None, unlabeled_run.label());
+
+    // Labels are unique per experiment
+    let res = experiment.run(|context| {
+        context.add_value_by_name(variable.name(), "duplicate");
+        context.set_label("baseline");
+        Ok(())
+    });
+    assert!(res.is_err());
+    assert_eq!(2, experiment.run_headers()?.len());
+
+    let labels = experiment
+        .all_runs()?
+        .iter()
+        .map(|run| run.label().map(str::to_owned))
+        .collect::<Vec<_>>();
+    assert_eq!(vec![Some("baseline".to_owned()), None], labels);
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_runs_from_multiple_threads() -> Result<()> {
+    // Connections are shared between threads through the connection pool, so concurrent experiments must neither
+    // deadlock nor interfere with each other
+    const NUM_THREADS: usize = 8;
+    const RUNS_PER_THREAD: usize = 5;
+
+    let handles = (0..NUM_THREADS)
+        .map(|_| {
+            std::thread::spawn(|| -> Result<()> {
+                let variable =
+                    VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+                let experiment = Experiment::new(
+                    random_string(16),
+                    random_string(32),
+                    random_string(16),
+                    [variable.clone()].into_iter().collect(),
+                )?;
+                for _ in 0..RUNS_PER_THREAD {
+                    experiment.run(|context| {
+                        context.add_value_by_name(variable.name(), random_string(8));
+                        Ok(())
+                    })?;
+                }
+                assert_eq!(RUNS_PER_THREAD, experiment.run_headers()?.len());
+                experiment.delete_from_database()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        handle.join().expect("Thread panicked")?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn concurrent_creation_of_same_experiment() -> Result<()> {
+    // All threads create the same experiment at once, and each pair of threads shares a new variable with another
+    // experiment, so both the experiment and the variables must be inserted only once without failing any thread
+    const NUM_THREADS: usize = 8;
+
+    let name = random_string(16);
+    let description = random_string(32);
+    let researcher = random_string(16);
+    let variables = (0..NUM_THREADS / 2)
+        .map(|_| VariableTemplate::new(random_string(16).into(), "".into(), "".into()))
+        .collect::<Vec<_>>();
+    let barrier = std::sync::Barrier::new(NUM_THREADS);
+
+    let (experiments, other_experiments) = std::thread::scope(|scope| {
+        let handles = (0..NUM_THREADS)
+            .map(|index| {
+                let (name, description, researcher, variables, barrier) =
+                    (&name, &description, &researcher, &variables, &barrier);
+                scope.spawn(move || -> Result<(Experiment, Experiment)> {
+                    let shared_variable = variables[index / 2].clone();
+                    barrier.wait();
+                    let experiment = Experiment::new(
+                        name.clone(),
+                        description.clone(),
+                        researcher.clone(),
+                        variables.iter().cloned().collect(),
+                    )?;
+                    let other_experiment = Experiment::new(
+                        random_string(16),
+                        random_string(32),
+                        random_string(16),
+                        [shared_variable].into_iter().collect(),
+                    )?;
+                    Ok((experiment, other_experiment))
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Thread panicked"))
+            .collect::<Result<(Vec<_>, Vec<_>)>>()
+    })?;
+
+    assert!(experiments
+        .iter()
+        .all(|experiment| experiment.id() == experiments[0].id()));
+    let mut client = connect_to_test_db()?;
+    let experiment_count: i64 = client
+        .query_one("SELECT COUNT(*) FROM experiments WHERE name = $1", &[&name])?
+        .get(0);
+    assert_eq!(1, experiment_count);
+    let variable_names = variables
+        .iter()
+        .map(|variable| variable.name().to_owned())
+        .collect::<Vec<_>>();
+    let variable_count: i64 = client
+        .query_one(
+            "SELECT COUNT(*) FROM variables WHERE name = ANY($1)",
+            &[&variable_names],
+        )?
+        .get(0);
+    assert_eq!(NUM_THREADS as i64 / 2, variable_count);
+
+    for experiment in other_experiments {
+        experiment.delete_from_database()?;
+    }
+    experiments
+        .into_iter()
+        .next()
+        .expect("At least one experiment")
+        .delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn run_many_inserts_all_runs() -> Result<()> {
+    // More runs than fit into a single INSERT statement, so that the batch has to be split
+    const NUM_RUNS: usize = 2500;
+
+    let index = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let optional = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let mut experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [index.clone(), optional.clone()].into_iter().collect(),
+    )?;
+    experiment.set_variable_optional(optional.name(), true)?;
+
+    // An existing run, so that run numbers of the batch have to continue from it
+    experiment.run(|context| {
+        context.add_value_by_name(index.name(), "single");
+        Ok(())
+    })?;
+
+    let runs = (0..NUM_RUNS).map(|run| {
+        let mut values = vec![(index.name(), run.to_string())];
+        if run % 2 == 0 {
+            values.push((optional.name(), "even".to_owned()));
+        }
+        values
+    });
+    let run_ids = experiment.run_many(runs)?;
+    assert_eq!(NUM_RUNS, run_ids.len());
+
+    let all_runs = experiment.all_runs()?;
+    assert_eq!(NUM_RUNS + 1, all_runs.len());
+    for (run_index, run) in all_runs.iter().skip(1).enumerate() {
+        assert_eq!(run_ids[run_index], run.id());
+        assert_eq!(run_index + 2, run.run_number());
+        let value_of = |name: &str| {
+            run.measurements()
+                .iter()
+                .find(|measurement| measurement.variable().template().name() == name)
+                .and_then(|measurement| measurement.value())
+        };
+        let expected_index = run_index.to_string();
+        assert_eq!(Some(expected_index.as_str()), value_of(index.name()));
+        let expected_optional = if run_index % 2 == 0 {
+            Some("even")
+        } else {
+            None
+        };
+        assert_eq!(expected_optional, value_of(optional.name()));
+    }
+
+    // Invalid runs are detected before anything is stored
+    assert!(experiment
+        .run_many([vec![("unknown variable", "1")]])
+        .is_err());
+    assert!(experiment.run_many([vec![(optional.name(), "1")]]).is_err());
+    assert_eq!(NUM_RUNS + 1, experiment.run_headers()?.len());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn experiments_can_be_found_by_tag() -> Result<()> {
+    add_experiment_tags_table()?;
+
+    let tag = random_string(16);
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        Default::default(),
+    )?;
+    let other_experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        Default::default(),
+    )?;
+    assert!(experiment.tags()?.is_empty());
+
+    experiment.add_tag(&tag)?;
+    experiment.add_tag(&tag)?;
+    experiment.add_tag("baseline")?;
+    assert_eq!(
+        [tag.clone(), "baseline".to_owned()]
+            .into_iter()
+            .collect::<std::collections::BTreeSet<_>>(),
+        experiment.tags()?
+    );
+    assert!(other_experiment.tags()?.is_empty());
+
+    let tagged = Experiment::with_tag(&tag)?;
+    assert_eq!(1, tagged.len());
+    assert_eq!(experiment.id(), tagged[0].id());
+
+    assert!(experiment.remove_tag(&tag)?);
+    assert!(!experiment.remove_tag(&tag)?);
+    assert!(Experiment::with_tag(&tag)?.is_empty());
+
+    // Tags must not prevent deleting the experiment
+    experiment.delete_from_database()?;
+    other_experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn archived_experiment_can_be_imported() -> Result<()> {
+    add_experiment_tags_table()?;
+
+    let required = VariableTemplate::new(random_string(16).into(), "".into(), "ms".into());
+    let optional = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let mut experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [required.clone(), optional.clone()].into_iter().collect(),
+    )?;
+    experiment.set_variable_optional(optional.name(), true)?;
+    experiment.add_tag("baseline")?;
+    experiment.run(|context| {
+        context.add_value_by_name(required.name(), 1);
+        context.add_value_by_name(optional.name(), "a value");
+        context.set_label("first");
+        Ok(())
+    })?;
+    experiment.run(|context| {
+        context.add_value_by_name(required.name(), 2);
+        Ok(())
+    })?;
+
+    let archive = experiment.to_archive()?;
+    assert_eq!(2, archive.runs.len());
+    assert_eq!(Some("first"), archive.runs[0].label.as_deref());
+    assert_eq!(None, archive.runs[1].measurements[optional.name()]);
+    let archive: ExperimentArchive = serde_json::from_str(&serde_json::to_string(&archive)?)?;
+
+    // Importing into the same database fails because the name is taken, but works under a new name with new IDs
+    assert!(Experiment::import_archive(&archive).is_err());
+    let mut renamed_archive = archive.clone();
+    renamed_archive.name = random_string(16);
+    let copy = Experiment::import_archive(&renamed_archive)?;
+    assert_ne!(experiment.id(), copy.id());
+    let copied_archive = copy.to_archive()?;
+    assert_eq!(2, copied_archive.runs.len());
+    for (original_run, copied_run) in archive.runs.iter().zip(copied_archive.runs.iter()) {
+        assert_ne!(original_run.id, copied_run.id);
+        assert_eq!(original_run.measurements, copied_run.measurements);
+    }
+    copy.delete_from_database()?;
+
+    // Without collisions, all IDs are preserved
+    experiment.delete_from_database()?;
+    let imported = Experiment::import_archive(&archive)?;
+    assert_eq!(archive, imported.to_archive()?);
+    imported.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn run_stream_reports_progress() -> Result<()> {
+    // With one measurement per run, the stream fetches the runs in three chunks
+    const NUM_RUNS: usize = 2500;
+
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )?;
+    experiment.run_many((0..NUM_RUNS).map(|run| [(variable.name(), run)]))?;
+
+    let mut progress = vec![];
+    let run_count = experiment
+        .stream_runs()?
+        .with_progress(|runs| progress.push(runs))
+        .count();
+    assert_eq!(NUM_RUNS, run_count);
+    // The last run of a chunk is only returned once the next chunk shows that it has no further measurements
+    assert_eq!(vec![0, 999, 1999], progress);
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn streamed_runs_match_all_runs() -> Result<()> {
+    // Enough measurements that the stream has to fetch multiple chunks
+    const NUM_RUNS: usize = 700;
+
+    let first = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let second = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [first.clone(), second.clone()].into_iter().collect(),
+    )?;
+    experiment.run_many((0..NUM_RUNS).map(|run| {
+        [
+            (first.name(), run.to_string()),
+            (second.name(), (run * 2).to_string()),
+        ]
+    }))?;
+
+    let all_runs = experiment.all_runs()?;
+    let streamed_runs = experiment.stream_runs()?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(NUM_RUNS, streamed_runs.len());
+    assert_eq!(all_runs.len(), streamed_runs.len());
+    for (expected, actual) in all_runs.iter().zip(streamed_runs.iter()) {
+        assert_eq!(expected.id(), actual.id());
+        assert_eq!(expected.run_number(), actual.run_number());
+        assert_eq!(expected.label(), actual.label());
+        let values = |run: &experiment_archiver::Run<'_>| {
+            run.measurements()
+                .iter()
+                .map(|measurement| measurement.value().map(str::to_owned))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(expected), values(actual));
+    }
+
+    // Dropping a stream before it is exhausted must not leave its connection in an unusable state
+    let first_run = experiment
+        .stream_runs()?
+        .next()
+        .expect("Stream must not be empty")?;
+    assert_eq!(all_runs[0].id(), first_run.id());
+    assert_eq!(NUM_RUNS, experiment.run_headers()?.len());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn run_can_be_recorded_in_multiple_stages() -> Result<()> {
+    let setup = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let result = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [setup.clone(), result.clone()].into_iter().collect(),
+    )?;
+
+    let run = experiment.begin_run();
+    run.add_value_by_name(setup.name(), "first stage");
+    run.set_label("staged");
+    // ... more work between the stages
+    run.add_value_by_name(result.name(), "second stage");
+    let run_id = run.finish()?;
+
+    let stored_run = experiment
+        .run_from_label("staged")?
+        .expect("Run not found by its label");
+    assert_eq!(run_id, stored_run.id());
+    let values = stored_run
+        .measurements()
+        .iter()
+        .map(|measurement| {
+            (
+                measurement.variable().template().name().to_owned(),
+                measurement.value().map(str::to_owned),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    assert_eq!(
+        Some(&Some("first stage".to_owned())),
+        values.get(setup.name())
+    );
+    assert_eq!(
+        Some(&Some("second stage".to_owned())),
+        values.get(result.name())
+    );
+
+    // Incomplete runs are rejected when finishing, and abandoned runs are never stored
+    let incomplete_run = experiment.begin_run();
+    incomplete_run.add_value_by_name(setup.name(), "only one stage");
+    assert!(incomplete_run.finish().is_err());
+    let abandoned_run = experiment.begin_run();
+    abandoned_run.add_value_by_name(setup.name(), "abandoned");
+    abandoned_run.add_value_by_name(result.name(), "abandoned");
+    drop(abandoned_run);
+    assert_eq!(1, experiment.count_runs()?);
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn run_parallel_stores_all_runs() -> Result<()> {
+    const NUM_RUNS: usize = 64;
+
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )?;
+
+    let counter = AtomicUsize::new(0);
+    let run_ids = experiment.run_parallel(NUM_RUNS, |context| {
+        let value = counter.fetch_add(1, Ordering::SeqCst);
+        context.add_value_by_name(variable.name(), value);
+        context.set_label(format!("run {value}"));
+        Ok(())
+    })?;
+    assert_eq!(NUM_RUNS, run_ids.len());
+
+    let all_runs = experiment.all_runs()?;
+    assert_eq!(
+        run_ids,
+        all_runs
+            .iter()
+            .map(|run| run.id().to_owned())
+            .collect::<Vec<_>>()
+    );
+    let values = all_runs
+        .iter()
+        .map(|run| run.measurements()[0].value().unwrap().parse::<usize>())
+        .collect::<Result<HashSet<_>, _>>()?;
+    assert_eq!((0..NUM_RUNS).collect::<HashSet<_>>(), values);
+    assert!(
+        all_runs
+            .iter()
+            .all(|run| run.label()
+                == Some(&format!("run {}", run.measurements()[0].value().unwrap())))
+    );
+
+    // A single failed execution discards all runs
+    assert!(experiment
+        .run_parallel(NUM_RUNS, |context| {
+            if counter.fetch_add(1, Ordering::SeqCst).is_multiple_of(10) {
+                anyhow::bail!("Execution failed");
+            }
+            context.add_value_by_name(variable.name(), 0);
+            Ok(())
+        })
+        .is_err());
+    assert_eq!(NUM_RUNS, experiment.run_headers()?.len());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn run_count_matches_fetched_runs() -> Result<()> {
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let name = random_string(16);
+    assert!(!Experiment::exists(&name)?);
+    let experiment = Experiment::new(
+        name.clone(),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )?;
+    assert!(Experiment::exists(&name)?);
+    assert_eq!(0, experiment.count_runs()?);
+
+    experiment.run_many((0..5).map(|run| [(variable.name(), run)]))?;
+    assert_eq!(5, experiment.count_runs()?);
+    assert_eq!(experiment.run_headers()?.len(), experiment.count_runs()?);
+    assert_eq!(experiment.all_runs()?.len(), experiment.count_runs()?);
+
+    experiment.delete_from_database()?;
+    assert!(!Experiment::exists(&name)?);
+
+    Ok(())
+}
+
+#[test]
+fn paged_run_headers_match_all_run_headers() -> Result<()> {
+    const NUM_RUNS: usize = 25;
+    const PAGE_SIZE: usize = 7;
+
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )?;
+    experiment.run_many((0..NUM_RUNS).map(|run| [(variable.name(), run)]))?;
+
+    let all_run_ids = experiment
+        .run_headers()?
+        .into_iter()
+        .map(|run| run.run_id)
+        .collect::<Vec<_>>();
+    let mut paged_run_ids = vec![];
+    for offset in (0..NUM_RUNS).step_by(PAGE_SIZE) {
+        let page = experiment.run_headers_page(Some(PAGE_SIZE), offset)?;
+        assert_eq!(PAGE_SIZE.min(NUM_RUNS - offset), page.len());
+        paged_run_ids.extend(page.into_iter().map(|run| run.run_id));
+    }
+    assert_eq!(all_run_ids, paged_run_ids);
+
+    assert_eq!(5, experiment.run_headers_page(None, 20)?.len());
+    assert!(experiment.run_headers_page(Some(10), NUM_RUNS)?.is_empty());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn run_headers_match_all_runs() -> Result<()> {
+    let variables: HashSet<_> = (0..3)
+        .map(|_| {
+            VariableTemplate::new(
+                random_string(16).into(),
+                random_string(32).into(),
+                random_string(8).into(),
+            )
+        })
+        .collect();
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        variables.clone(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    for _ in 0..3 {
+        experiment.run(|context| {
+            for variable in &variables {
+                context.add_value_by_name(variable.name(), random_string(8));
+            }
+            Ok(())
+        })?;
+    }
+
+    let run_headers = experiment.run_headers()?;
+    let all_runs = experiment.all_runs()?;
+    assert_eq!(
+        all_runs.iter().map(|run| run.id()).collect::<Vec<_>>(),
+        run_headers
+            .iter()
+            .map(|run| run.run_id.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![1, 2, 3],
+        run_headers
+            .iter()
+            .map(|run| run.run_number)
+            .collect::<Vec<_>>()
+    );
+    assert!(run_headers
+        .iter()
+        .all(|run| run.experiment_id == experiment.id()));
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn convert_measurements_into_other_units() -> Result<()> {
+    let runtime = VariableTemplate::new(random_string(16).into(), "".into(), "msec".into());
+    let host = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [runtime.clone(), host.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    experiment.run(|context| {
+        context.add_value_by_name(runtime.name(), 1500);
+        context.add_value_by_name(host.name(), "node1");
+        Ok(())
+    })?;
+
+    let runs = experiment.all_runs()?;
+    let measurement_of = |variable: &VariableTemplate| {
+        runs[0]
+            .measurements()
+            .iter()
+            .find(|measurement| measurement.variable().template().name() == variable.name())
+            .expect("Measurement not found")
+    };
+    assert_eq!(Some(1.5), measurement_of(&runtime).value_in_unit("s")?);
+    assert!(measurement_of(&runtime).value_in_unit("B").is_err());
+    assert!(measurement_of(&host).value_in_unit("s").is_err());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn numeric_values_are_stored_in_typed_column() -> Result<()> {
+    // The test database already has the numeric_value column, so the migration must be a no-op
+    add_numeric_value_column()?;
+
+    let runtime = VariableTemplate::new(random_string(16).into(), "".into(), "ms".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [runtime.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    experiment.run_many([
+        [(runtime.name(), "100")],
+        [(runtime.name(), "1e3")],
+        [(runtime.name(), "unknown")],
+    ])?;
+    experiment.run(|context| {
+        context.add_value_by_name(runtime.name(), 2.5);
+        Ok(())
+    })?;
+
+    let mut client = connect_to_test_db()?;
+    let numeric_values = client
+        .query(
+            "SELECT numeric_value FROM measurements INNER JOIN experiment_runs ON measurements.runid = experiment_runs.id WHERE measurements.experimentid = $1 ORDER BY runnumber",
+            &[&experiment.id()],
+        )?
+        .iter()
+        .map(|row| row.get::<_, Option<f64>>(0))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![Some(100.0), Some(1000.0), None, Some(2.5)],
+        numeric_values
+    );
+
+    // Numeric predicates only look at the typed column, so changing it changes which runs match
+    client.execute(
+        "UPDATE measurements SET numeric_value = 5000 WHERE experimentid = $1 AND value = '100'",
+        &[&experiment.id()],
+    )?;
+    let matching_run_numbers = experiment
+        .run_headers_matching(&[MeasurementPredicate::new(
+            runtime.name(),
+            ComparisonOperator::Gt,
+            2000,
+        )])?
+        .iter()
+        .map(|run| run.run_number)
+        .collect::<Vec<_>>();
+    assert_eq!(vec![1], matching_run_numbers);
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn filter_runs_by_measurements() -> Result<()> {
+    let runtime = VariableTemplate::new(random_string(16).into(), "".into(), "ms".into());
+    let host = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [runtime.clone(), host.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    // Compared as strings, "1000" would be smaller than "600"
+    experiment.run_many([
+        [(runtime.name(), "100"), (host.name(), "node1")],
+        [(runtime.name(), "600"), (host.name(), "node2")],
+        [(runtime.name(), "1000"), (host.name(), "node1")],
+        [(runtime.name(), "unknown"), (host.name(), "node2")],
+    ])?;
+
+    let matching_run_numbers = |predicates: &[MeasurementPredicate]| -> Result<Vec<usize>> {
+        Ok(experiment
+            .run_headers_matching(predicates)?
+            .iter()
+            .map(|run| run.run_number)
+            .collect())
+    };
+
+    assert_eq!(vec![1, 2, 3, 4], matching_run_numbers(&[])?);
+    assert_eq!(
+        vec![2, 3],
+        matching_run_numbers(&[MeasurementPredicate::new(
+            runtime.name(),
+            ComparisonOperator::Gt,
+            500
+        )])?
+    );
+    assert_eq!(
+        vec![1, 2],
+        matching_run_numbers(&[format!("{}<=600.0", runtime.name()).parse()?])?
+    );
+    assert_eq!(
+        vec![1, 3],
+        matching_run_numbers(&[MeasurementPredicate::new(
+            host.name(),
+            ComparisonOperator::Eq,
+            "node1"
+        )])?
+    );
+    assert_eq!(
+        vec![4],
+        matching_run_numbers(&[MeasurementPredicate::new(
+            runtime.name(),
+            ComparisonOperator::Eq,
+            "unknown"
+        )])?
+    );
+    // All predicates must be satisfied
+    assert_eq!(
+        vec![2],
+        matching_run_numbers(&[
+            MeasurementPredicate::new(runtime.name(), ComparisonOperator::Gt, 500),
+            MeasurementPredicate::new(host.name(), ComparisonOperator::Ne, "node1"),
+        ])?
+    );
+    assert!(matching_run_numbers(&[MeasurementPredicate::new(
+        random_string(16),
+        ComparisonOperator::Eq,
+        "node1"
+    )])?
+    .is_empty());
+
+    let runs = experiment.runs_matching(&[MeasurementPredicate::new(
+        host.name(),
+        ComparisonOperator::Eq,
+        "node2",
+    )])?;
+    assert_eq!(
+        vec![2, 4],
+        runs.iter().map(|run| run.run_number()).collect::<Vec<_>>()
+    );
+    assert_eq!(2, runs[0].measurements().len());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+fn first_fixed_clock() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000)
+}
+
+fn second_fixed_clock() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+}
+
+#[test]
+fn runs_use_timestamps_of_clock() -> Result<()> {
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    set_clock(second_fixed_clock);
+    experiment.run(|context| {
+        context.add_value_by_name(variable.name(), 1);
+        Ok(())
+    })?;
+    set_clock(first_fixed_clock);
+    experiment.run_many([[(variable.name(), 2)], [(variable.name(), 3)]])?;
+    set_clock(SystemTime::now);
+
+    let timestamps = experiment
+        .run_headers()?
+        .iter()
+        .map(|run| run.timestamp)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            second_fixed_clock(),
+            first_fixed_clock(),
+            first_fixed_clock()
+        ],
+        timestamps
+    );
+    // Fetched runs carry the timestamp of the run itself
+    let run_timestamps = experiment
+        .all_runs()?
+        .iter()
+        .map(|run| run.timestamp())
+        .collect::<Vec<_>>();
+    assert_eq!(timestamps, run_timestamps);
+    let streamed_run_timestamps = experiment
+        .stream_runs()?
+        .map(|run| run.map(|run| run.timestamp()))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(timestamps, streamed_run_timestamps);
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn latest_run_has_latest_timestamp() -> Result<()> {
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    assert!(experiment.latest_run()?.is_none());
+
+    // The latest run is determined by its timestamp, not by its run number
+    set_clock(first_fixed_clock);
+    experiment.run_many([[(variable.name(), 1)]])?;
+    set_clock(second_fixed_clock);
+    experiment.run_many([[(variable.name(), 2)]])?;
+    set_clock(first_fixed_clock);
+    experiment.run_many([[(variable.name(), 3)]])?;
+    set_clock(SystemTime::now);
+
+    let latest_run = experiment
+        .latest_run()?
+        .expect("Experiment with runs must have a latest run");
+    assert_eq!(2, latest_run.run_number());
+    assert_eq!(1, latest_run.measurements().len());
+    assert_eq!(Some("2"), latest_run.measurements()[0].value());
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn delete_runs_in_date_range() -> Result<()> {
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+
+    set_clock(first_fixed_clock);
+    experiment.run_many([[(variable.name(), 1)], [(variable.name(), 2)]])?;
+    set_clock(second_fixed_clock);
+    experiment.run_many([[(variable.name(), 3)]])?;
+    set_clock(SystemTime::now);
+    experiment.run(|context| {
+        context.add_value_by_name(variable.name(), 4);
+        Ok(())
+    })?;
+
+    // The end of the range is exclusive, so the run at the second timestamp is kept
+    let deleted_runs =
+        experiment.delete_runs_in_date_range(SystemTime::UNIX_EPOCH..second_fixed_clock())?;
+    assert_eq!(2, deleted_runs);
+    let remaining_runs = experiment.all_runs()?;
+    assert_eq!(
+        vec![3, 4],
+        remaining_runs
+            .iter()
+            .map(|run| run.run_number())
+            .collect::<Vec<_>>()
+    );
+    assert!(remaining_runs
+        .iter()
+        .all(|run| run.measurements().len() == 1));
+
+    // Nothing is deleted if no run lies within the range
+    assert_eq!(
+        0,
+        experiment.delete_runs_in_date_range(first_fixed_clock()..second_fixed_clock())?
+    );
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn measurements_are_sorted_by_variable_name() -> Result<()> {
+    const NUM_VARIABLES: usize = 8;
+    let variables = (0..NUM_VARIABLES)
+        .map(|_| {
+            VariableTemplate::new(
+                random_string(16).into(),
+                random_string(32).into(),
+                random_string(8).into(),
+            )
+        })
+        .collect();
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        variables,
+    )
+    .context("Failed to create new Experiment")?;
+    let run_id = experiment
+        .run(|context| {
+            for variable in experiment.variables() {
+                context.add_value_by_name(variable.template().name(), random_string(8));
+            }
+            Ok(())
+        })
+        .context("Experiment run failed")?;
+
+    let mut expected_names = experiment
+        .variables()
+        .map(|variable| variable.template().name().to_owned())
+        .collect::<Vec<_>>();
+    expected_names.sort();
+    let names_of = |measurements: &[experiment_archiver::Measurement]| {
+        measurements
+            .iter()
+            .map(|measurement| measurement.variable().template().name().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        expected_names,
+        names_of(&experiment.measurements_for_run(&run_id)?)
+    );
+    let run = experiment
+        .run_from_id(&run_id)?
+        .expect("Run not found in database");
+    assert_eq!(expected_names, names_of(run.measurements()));
+    let all_runs = experiment.all_runs()?;
+    assert_eq!(expected_names, names_of(all_runs[0].measurements()));
+
+    Ok(())
+}
+
+#[test]
+fn ping_database() -> Result<()> {
+    ping().context("Test database is not reachable")
+}
+
+#[test]
+fn database_schema_exists() -> Result<()> {
+    assert_eq!(Vec::<String>::new(), missing_schema_columns()?);
+    assert!(schema_exists()?);
+    Ok(())
+}
@@ -0,0 +1,53 @@
+/**
+ * Tests for schema migrations that change existing tables. These tests temporarily remove columns from the test
+ * database, which would break concurrently running tests. Therefore they live in a separate test binary
+ */
+mod common;
+
+use anyhow::{Context, Result};
+use common::{connect_to_test_db, random_string};
+use experiment_archiver::{add_numeric_value_column, Experiment, VariableTemplate};
+
+#[test]
+fn numeric_value_column_is_filled_for_existing_measurements() -> Result<()> {
+    let runtime = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let huge = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let host = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [runtime.clone(), huge.clone(), host.clone()]
+            .into_iter()
+            .collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    experiment.run(|context| {
+        context.add_value_by_name(runtime.name(), " 2.5 ");
+        // Matches the pattern for numbers, but is out of range for a double
+        context.add_value_by_name(huge.name(), "1e999");
+        context.add_value_by_name(host.name(), "node1");
+        Ok(())
+    })?;
+
+    let mut client = connect_to_test_db()?;
+    client.batch_execute("ALTER TABLE public.measurements DROP COLUMN IF EXISTS numeric_value;")?;
+    add_numeric_value_column()
+        .context("Migration must succeed for values that are out of range")?;
+
+    let numeric_value_of = |client: &mut postgres::Client, variable: &VariableTemplate| {
+        client
+            .query_one(
+                "SELECT numeric_value FROM measurements INNER JOIN variables ON measurements.variableid = variables.id WHERE experimentid = $1 AND variables.name = $2",
+                &[&experiment.id(), &variable.name()],
+            )
+            .map(|row| row.get::<_, Option<f64>>(0))
+    };
+    assert_eq!(Some(2.5), numeric_value_of(&mut client, &runtime)?);
+    assert_eq!(None, numeric_value_of(&mut client, &huge)?);
+    assert_eq!(None, numeric_value_of(&mut client, &host)?);
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+/**
+ * Tests for custom unique ID generators and the handling of ID collisions. The unique ID generator is global, and these
+ * tests install generators that deliberately return IDs that already exist, which would break concurrently running
+ * tests. Therefore they live in a separate test binary and install their generators through `use_generator`. Tests
+ * that migrate the schema do so in a `ScratchDatabase`, so the shared test database keeps the schema of
+ * `test_data/dbschema.sql`
+ */
+mod common;
+
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{Context, Result};
+use common::{connect_to_database, connect_to_test_db, random_string};
+use experiment_archiver::{
+    add_experiment_tags_table, gen_alphanumeric_id, set_unique_id_generator, widen_id_columns,
+    Experiment, UniqueIdGenerator, VariableTemplate,
+};
+use rand::{thread_rng, Rng};
+
+/// Serializes the tests of this binary, since each of them replaces the global unique ID generator
+static GENERATOR_LOCK: Mutex<()> = Mutex::new(());
+
+/// Restores the default unique ID generator when dropped, even if the test panics
+struct GeneratorGuard {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Drop for GeneratorGuard {
+    fn drop(&mut self) {
+        set_unique_id_generator(gen_alphanumeric_id);
+    }
+}
+
+/// Installs `generator` as the unique ID generator until the returned guard is dropped
+fn use_generator(generator: UniqueIdGenerator) -> GeneratorGuard {
+    let lock = GENERATOR_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    set_unique_id_generator(generator);
+    GeneratorGuard { _lock: lock }
+}
+
+fn gen_hex_id(length: usize) -> String {
+    let mut rng = thread_rng();
+    (0..length)
+        .map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap())
+        .collect()
+}
+
+/// The ID that `colliding_id_generator` returns next. Once it was returned, random IDs are generated again
+static NEXT_ID: Mutex<Option<String>> = Mutex::new(None);
+
+fn colliding_id_generator() -> String {
+    NEXT_ID
+        .lock()
+        .unwrap()
+        .take()
+        .unwrap_or_else(gen_alphanumeric_id)
+}
+
+#[test]
+fn colliding_ids_are_regenerated() -> Result<()> {
+    let _generator = use_generator(colliding_id_generator);
+
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )?;
+    let run_id = experiment.run(|context| {
+        context.add_value_by_name(variable.name(), 1);
+        Ok(())
+    })?;
+
+    *NEXT_ID.lock().unwrap() = Some(experiment.id().to_owned());
+    let other_experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        Default::default(),
+    )?;
+    assert!(NEXT_ID.lock().unwrap().is_none());
+    assert_ne!(experiment.id(), other_experiment.id());
+
+    *NEXT_ID.lock().unwrap() = Some(run_id.clone());
+    let other_run_id = experiment.run(|context| {
+        context.add_value_by_name(variable.name(), 2);
+        Ok(())
+    })?;
+    assert!(NEXT_ID.lock().unwrap().is_none());
+    assert_ne!(run_id, other_run_id);
+
+    *NEXT_ID.lock().unwrap() = Some(run_id.clone());
+    let batch_run_ids = experiment.run_many([[(variable.name(), 3)], [(variable.name(), 4)]])?;
+    assert!(!batch_run_ids.contains(&run_id));
+    assert_eq!(4, experiment.count_runs()?);
+
+    other_experiment.delete_from_database()?;
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+#[test]
+fn custom_unique_id_generator() -> Result<()> {
+    let _generator = use_generator(|| gen_hex_id(16));
+
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )
+    .context("Failed to create new Experiment")?;
+    let run_id = experiment
+        .run(|context| {
+            context.add_value_by_name(variable.name(), random_string(8));
+            Ok(())
+        })
+        .context("Experiment run failed")?;
+
+    let is_hex_id = |id: &str| id.len() == 16 && id.chars().all(|c| c.is_ascii_hexdigit());
+    assert!(is_hex_id(experiment.id()));
+    assert!(is_hex_id(&run_id));
+
+    experiment.delete_from_database()?;
+
+    Ok(())
+}
+
+/// A database with the schema of `test_data/dbschema.sql` that exists for the duration of a single test, so that the
+/// test can migrate the schema without affecting the shared test database. While it exists, `PSQL_DBNAME` points to it,
+/// so it must only be created while `GENERATOR_LOCK` is held
+struct ScratchDatabase {
+    name: String,
+    shared_dbname: String,
+}
+
+impl ScratchDatabase {
+    fn create() -> Result<Self> {
+        let shared_dbname = std::env::var("PSQL_DBNAME")?;
+        let name = format!("scratch_{}", random_string(16).to_lowercase());
+        connect_to_test_db()?
+            .batch_execute(&format!("CREATE DATABASE {name};"))
+            .context("Failed to create scratch database")?;
+        let scratch_database = Self {
+            name,
+            shared_dbname,
+        };
+        connect_to_database(&scratch_database.name)?
+            .batch_execute(include_str!("../test_data/dbschema.sql"))
+            .context("Failed to create schema in scratch database")?;
+        std::env::set_var("PSQL_DBNAME", &scratch_database.name);
+        Ok(scratch_database)
+    }
+}
+
+impl Drop for ScratchDatabase {
+    fn drop(&mut self) {
+        std::env::set_var("PSQL_DBNAME", &self.shared_dbname);
+        // Idle connections of the library might still be open, so they have to be closed forcefully
+        let dropped = connect_to_test_db().and_then(|mut client| {
+            client.batch_execute(&format!(
+                "DROP DATABASE IF EXISTS {} WITH (FORCE);",
+                self.name
+            ))?;
+            Ok(())
+        });
+        if let Err(why) = dropped {
+            eprintln!("Failed to drop scratch database {}: {why:#}", self.name);
+        }
+    }
+}
+
+#[test]
+fn widened_id_columns_hold_longer_ids() -> Result<()> {
+    let _generator = use_generator(|| gen_hex_id(32));
+    let _database = ScratchDatabase::create()?;
+
+    assert!(widen_id_columns(8).is_err());
+    widen_id_columns(32)?;
+    // Databases that are migrated to tags after widening their ID columns must be able to tag experiments with long IDs
+    connect_to_test_db()?.batch_execute("DROP TABLE public.experiment_tags;")?;
+    add_experiment_tags_table()?;
+
+    let variable = VariableTemplate::new(random_string(16).into(), "".into(), "".into());
+    let experiment = Experiment::new(
+        random_string(16),
+        random_string(32),
+        random_string(16),
+        [variable.clone()].into_iter().collect(),
+    )
+    .context("Failed to create experiment with long ID")?;
+    let run_id = experiment
+        .run(|context| {
+            context.add_value_by_name(variable.name(), 1);
+            Ok(())
+        })
+        .context("Failed to store run with long ID")?;
+    experiment
+        .add_tag("long-id")
+        .context("Failed to tag experiment with long ID")?;
+    assert_eq!(32, experiment.id().len());
+    assert_eq!(32, run_id.len());
+    assert_eq!(1, experiment.count_runs()?);
+
+    Ok(())
+}